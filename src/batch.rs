@@ -0,0 +1,219 @@
+use std::ops::Range;
+
+use crate::{
+    buffer::GrowableBuffer,
+    graphics::{Graphics, Layer, LayerFilter, Quad, Renderable},
+};
+
+// Quads the vertex buffer can hold before its first reallocation.
+const INITIAL_CAPACITY: usize = 64;
+
+/// Handle to a range of quads reserved in a [`QuadBatch`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchSlot(usize);
+
+#[derive(Debug)]
+struct SlotRange {
+    offset: usize,
+    capacity: usize,
+    used: usize,
+}
+
+/// Single instance buffer shared by multiple elements, drawn once per layer
+/// so the quads are submitted from the bottom layer up.
+///
+/// Every element reserves a slot and writes its quads only when they change.
+/// Slots grow when written past their capacity, moving the following slots.
+/// Only the ranges of quads changed since the last upload are sent to
+/// the GPU.
+/// Unused quads of a slot are zeroed, which makes them degenerate.
+pub struct QuadBatch {
+    quads: Vec<Quad>,
+    slots: Vec<SlotRange>,
+    // Ranges of quads changed since the last upload.
+    dirty: Vec<Range<usize>>,
+
+    vertex_buffer: GrowableBuffer<Quad>,
+    instances: u32,
+    layers: LayerFilter,
+}
+
+impl QuadBatch {
+    pub fn new(gfx: &Graphics, label: &'static str) -> Self {
+        Self {
+            quads: Vec::new(),
+            slots: Vec::new(),
+            dirty: Vec::new(),
+            vertex_buffer: GrowableBuffer::new(
+                gfx,
+                label,
+                wgpu::BufferUsages::VERTEX,
+                INITIAL_CAPACITY,
+            ),
+            instances: 0,
+            layers: LayerFilter::new(gfx),
+        }
+    }
+
+    /// Reserves room for `capacity` quads. Within a layer, slots are drawn
+    /// in the order they were allocated in.
+    pub fn allocate(&mut self, capacity: usize) -> BatchSlot {
+        let offset = self.quads.len();
+        self.quads
+            .resize(offset + capacity, bytemuck::Zeroable::zeroed());
+        self.slots.push(SlotRange {
+            offset,
+            capacity,
+            used: 0,
+        });
+        BatchSlot(self.slots.len() - 1)
+    }
+
+    /// Number of quads the slot can hold before it has to grow.
+    pub fn capacity(&self, slot: BatchSlot) -> usize {
+        self.slots[slot.0].capacity
+    }
+
+    /// Returns all quads of the slot, including the unused ones.
+    pub fn slot_quads(&self, slot: BatchSlot) -> &[Quad] {
+        let range = &self.slots[slot.0];
+        &self.quads[range.offset..range.offset + range.capacity]
+    }
+
+    /// Replaces the quads of the slot, growing it if needed.
+    pub fn write(&mut self, slot: BatchSlot, quads: &[Quad]) {
+        self.grow(slot, quads.len());
+        let range = &mut self.slots[slot.0];
+        let count = quads.len();
+
+        let start = range.offset;
+        let changed_end = start + count.max(range.used);
+        self.quads[start..start + count].copy_from_slice(&quads[..count]);
+        for quad in &mut self.quads[start + count..changed_end] {
+            *quad = bytemuck::Zeroable::zeroed();
+        }
+        range.used = count;
+
+        self.dirty.push(start..changed_end);
+    }
+
+    /// Replaces a single quad of the slot, `index` being relative to
+    /// the start of the slot.
+    pub fn write_at(&mut self, slot: BatchSlot, index: usize, quad: Quad) {
+        self.grow(slot, index + 1);
+        let range = &mut self.slots[slot.0];
+
+        let position = range.offset + index;
+        self.quads[position] = quad;
+        range.used = range.used.max(index + 1);
+
+        self.dirty.push(position..position + 1);
+    }
+
+    /// Sends the changed quads to the GPU.
+    pub fn upload(&mut self, gfx: &Graphics) {
+        if self.vertex_buffer.reserve(gfx, self.quads.len()) {
+            self.dirty.clear();
+            self.dirty.push(0..self.quads.len());
+        }
+
+        for dirty in Self::merge_ranges(std::mem::take(&mut self.dirty)) {
+            let start = dirty.start;
+            self.vertex_buffer.write_at(gfx, start, &self.quads[dirty]);
+        }
+
+        self.instances = self
+            .slots
+            .iter()
+            .filter(|range| range.used > 0)
+            .map(|range| range.offset + range.used)
+            .max()
+            .unwrap_or(0) as u32;
+    }
+
+    /// Makes room for at least `capacity` quads in the slot, doubling its
+    /// capacity and moving the following slots back.
+    fn grow(&mut self, slot: BatchSlot, capacity: usize) {
+        let range = &mut self.slots[slot.0];
+        if capacity <= range.capacity {
+            return;
+        }
+        let new_capacity = capacity.max(range.capacity * 2);
+        let added = new_capacity - range.capacity;
+        let end = range.offset + range.capacity;
+        range.capacity = new_capacity;
+
+        self.quads
+            .splice(end..end, vec![bytemuck::Zeroable::zeroed(); added]);
+        for range in &mut self.slots[slot.0 + 1..] {
+            range.offset += added;
+        }
+        self.dirty.push(end..self.quads.len());
+    }
+
+    /// Sorts the ranges and joins the overlapping and adjacent ones.
+    fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges.into_iter().filter(|range| !range.is_empty()) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => {
+                    last.end = last.end.max(range.end)
+                }
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+impl Renderable for QuadBatch {
+    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        if self.instances > 0 {
+            rpass.set_vertex_buffer(0, self.vertex_buffer.slice());
+            for layer in Layer::ALL {
+                self.layers.select(rpass, layer);
+                rpass.draw(0..4, 0..self.instances);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ranges are written as `(start, end)` pairs.
+    fn merge(ranges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let ranges = ranges.iter().map(|&(start, end)| start..end).collect();
+        QuadBatch::merge_ranges(ranges)
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn merge_adjacent() {
+        assert_eq!(merge(&[(0, 4), (4, 6)]), [(0, 6)]);
+        assert_eq!(merge(&[(4, 6), (0, 4), (6, 7)]), [(0, 7)]);
+    }
+
+    #[test]
+    fn merge_overlapping() {
+        assert_eq!(merge(&[(0, 5), (3, 8)]), [(0, 8)]);
+        assert_eq!(merge(&[(2, 3), (0, 10)]), [(0, 10)]);
+        assert_eq!(merge(&[(1, 2), (1, 2)]), [(1, 2)]);
+    }
+
+    #[test]
+    fn keep_disjoint() {
+        assert_eq!(merge(&[(6, 8), (0, 2), (3, 4)]), [(0, 2), (3, 4), (6, 8)]);
+    }
+
+    #[test]
+    fn drop_empty() {
+        assert_eq!(merge(&[]), []);
+        assert_eq!(merge(&[(3, 3), (5, 5)]), []);
+        assert_eq!(merge(&[(0, 2), (2, 2), (4, 6)]), [(0, 2), (4, 6)]);
+    }
+}
@@ -0,0 +1,111 @@
+use std::marker::PhantomData;
+
+use crate::graphics::Graphics;
+
+/// GPU buffer of `T` elements which reallocates itself, doubling its
+/// capacity, when written past its end.
+pub struct GrowableBuffer<T: bytemuck::Pod> {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    _element: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> GrowableBuffer<T> {
+    pub fn new(
+        gfx: &Graphics,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        let capacity = capacity.max(1);
+        Self {
+            label,
+            usage,
+            buffer: Self::create_buffer(gfx, label, usage, capacity),
+            capacity,
+            _element: PhantomData,
+        }
+    }
+
+    /// Makes room for at least `capacity` elements. Returns `true` if
+    /// the buffer was reallocated, which discards its contents.
+    pub fn reserve(&mut self, gfx: &Graphics, capacity: usize) -> bool {
+        let new_capacity = grown_capacity(self.capacity, capacity);
+        if new_capacity == self.capacity {
+            return false;
+        }
+        self.buffer =
+            Self::create_buffer(gfx, self.label, self.usage, new_capacity);
+        self.capacity = new_capacity;
+        true
+    }
+
+    /// Writes `data` starting at the element `offset`. The caller has to
+    /// reserve enough room first.
+    pub fn write_at(&self, gfx: &Graphics, offset: usize, data: &[T]) {
+        assert!(
+            offset + data.len() <= self.capacity,
+            "{}: write past the end of the buffer",
+            self.label
+        );
+        if data.is_empty() {
+            return;
+        }
+        gfx.queue.write_buffer(
+            &self.buffer,
+            (offset * std::mem::size_of::<T>()) as wgpu::BufferAddress,
+            bytemuck::cast_slice(data),
+        );
+    }
+
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+
+    fn create_buffer(
+        gfx: &Graphics,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> wgpu::Buffer {
+        gfx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (std::mem::size_of::<T>() * capacity) as wgpu::BufferAddress,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+}
+
+/// Doubles `capacity` until it holds at least `required` elements.
+fn grown_capacity(capacity: usize, required: usize) -> usize {
+    let mut capacity = capacity.max(1);
+    while capacity < required {
+        capacity *= 2;
+    }
+    capacity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_kept_when_large_enough() {
+        assert_eq!(grown_capacity(64, 0), 64);
+        assert_eq!(grown_capacity(64, 10), 64);
+        assert_eq!(grown_capacity(64, 64), 64);
+    }
+
+    #[test]
+    fn capacity_doubles() {
+        assert_eq!(grown_capacity(64, 65), 128);
+        assert_eq!(grown_capacity(64, 128), 128);
+        assert_eq!(grown_capacity(64, 129), 256);
+        assert_eq!(grown_capacity(3, 1000), 1536);
+        assert_eq!(grown_capacity(0, 5), 8);
+    }
+}
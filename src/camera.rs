@@ -0,0 +1,235 @@
+use wgpu::util::DeviceExt;
+
+use crate::{graphics::Graphics, settings::Settings};
+
+// Size of a tile in pixels in retro mode.
+const RETRO_TILE_PIXELS: u32 = 8;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+/// Size the game is drawn at and how it's fitted into the window.
+///
+/// In retro mode the game is drawn with a fixed number of pixels per tile,
+/// then scaled up to the window by a whole factor, so every tile stays the
+/// same size whatever the size of the window. The window is left with
+/// a black border of less than one scaled pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    // Size of the drawn game in pixels.
+    pub size: [u32; 2],
+    // Window pixels per game pixel.
+    pub scale: u32,
+    // Top left corner of the scaled game in the window.
+    pub offset: [u32; 2],
+    // Fixed tile size, or none to fit the view size into the window.
+    pub pixels_per_unit: Option<f32>,
+}
+
+impl Viewport {
+    pub fn new(gfx: &Graphics, settings: &Settings) -> Self {
+        let window = [gfx.config.width, gfx.config.height];
+        if !settings.effects.retro {
+            return Self {
+                size: window,
+                scale: 1,
+                offset: [0, 0],
+                pixels_per_unit: None,
+            };
+        }
+
+        // The scale which shows the view size closest to the one set.
+        let view_pixels = settings.view_size * RETRO_TILE_PIXELS;
+        let scale = (window[0].min(window[1]) as f32 / view_pixels as f32)
+            .round()
+            .max(1.0) as u32;
+        let size = window.map(|pixels| (pixels / scale).max(1));
+        Self {
+            size,
+            scale,
+            offset: [
+                window[0].saturating_sub(size[0] * scale) / 2,
+                window[1].saturating_sub(size[1] * scale) / 2,
+            ],
+            pixels_per_unit: Some(RETRO_TILE_PIXELS as f32),
+        }
+    }
+
+    /// Whether the game has to be scaled up to fill the window.
+    pub fn is_scaled(&self) -> bool {
+        self.scale > 1
+    }
+}
+
+/// Orthographic camera turning world or pixel positions into clip space.
+///
+/// Geometry is kept in its own units and only the uniform buffer changes
+/// when the window is resized or the camera moves.
+pub struct Camera {
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    // Clip space position is `pos * scale + translation`.
+    scale: [f32; 2],
+    translation: [f32; 2],
+    // Size in pixels of the target the camera draws into.
+    target_size: [f32; 2],
+}
+
+impl Camera {
+    pub fn bind_group_layout(gfx: &Graphics) -> wgpu::BindGroupLayout {
+        gfx.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Camera Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            })
+    }
+
+    pub fn new(gfx: &Graphics, layout: &wgpu::BindGroupLayout) -> Self {
+        let scale = [1.0, 1.0];
+        let translation = [0.0, 0.0];
+        let buffer =
+            gfx.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Camera Buffer"),
+                    contents: bytemuck::cast_slice(&[Self::uniform(
+                        scale,
+                        translation,
+                    )]),
+                    usage: wgpu::BufferUsages::UNIFORM
+                        | wgpu::BufferUsages::COPY_DST,
+                });
+        let bind_group =
+            gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Camera Bind Group"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+
+        Self {
+            buffer,
+            bind_group,
+            scale,
+            translation,
+            target_size: [1.0, 1.0],
+        }
+    }
+
+    /// Centers the camera on the world position `center`, one world unit
+    /// taking up `pixels_per_unit` pixels of a target `target_size` pixels
+    /// large.
+    pub fn look_at(
+        &mut self,
+        gfx: &Graphics,
+        target_size: [u32; 2],
+        center: [f32; 2],
+        pixels_per_unit: f32,
+    ) {
+        self.target_size = target_size.map(|size| size as f32);
+        self.scale = [
+            2.0 * pixels_per_unit / self.target_size[0],
+            2.0 * pixels_per_unit / self.target_size[1],
+        ];
+        self.translation =
+            [-center[0] * self.scale[0], -center[1] * self.scale[1]];
+        self.write(gfx);
+    }
+
+    /// Maps positions in pixels, from the top left corner of the window,
+    /// to clip space.
+    pub fn screen(&mut self, gfx: &Graphics) {
+        self.target_size = [gfx.config.width as f32, gfx.config.height as f32];
+        self.scale = [2.0 / self.target_size[0], -2.0 / self.target_size[1]];
+        self.translation = [-1.0, 1.0];
+        self.write(gfx);
+    }
+
+    /// Returns the position of `pos` in pixels from the top left corner
+    /// of the target.
+    pub fn to_pixels(&self, pos: [f32; 2]) -> [f32; 2] {
+        let clip = [
+            pos[0] * self.scale[0] + self.translation[0],
+            pos[1] * self.scale[1] + self.translation[1],
+        ];
+        [
+            (clip[0] + 1.0) / 2.0 * self.target_size[0],
+            (1.0 - clip[1]) / 2.0 * self.target_size[1],
+        ]
+    }
+
+    /// Returns the bottom left and the top right corner of the visible
+    /// world rectangle.
+    pub fn visible_rect(&self) -> ([f32; 2], [f32; 2]) {
+        let bottom_left = self.to_world([0.0, self.target_size[1]]);
+        let top_right = self.to_world([self.target_size[0], 0.0]);
+        (bottom_left, top_right)
+    }
+
+    /// Returns the world position of `pixels`, measured from the top left
+    /// corner of the target.
+    pub fn to_world(&self, pixels: [f32; 2]) -> [f32; 2] {
+        let clip = [
+            pixels[0] / self.target_size[0] * 2.0 - 1.0,
+            1.0 - pixels[1] / self.target_size[1] * 2.0,
+        ];
+        [
+            (clip[0] - self.translation[0]) / self.scale[0],
+            (clip[1] - self.translation[1]) / self.scale[1],
+        ]
+    }
+
+    /// Returns the x, y, width and height in pixels of the world rectangle
+    /// from `min` to `max`, clamped to the target.
+    pub fn scissor_rect(&self, min: [f32; 2], max: [f32; 2]) -> [u32; 4] {
+        let clamp = |pixels: [f32; 2]| {
+            [
+                pixels[0].round().clamp(0.0, self.target_size[0]) as u32,
+                pixels[1].round().clamp(0.0, self.target_size[1]) as u32,
+            ]
+        };
+        let top_left = clamp(self.to_pixels([min[0], max[1]]));
+        let bottom_right = clamp(self.to_pixels([max[0], min[1]]));
+        [
+            top_left[0],
+            top_left[1],
+            bottom_right[0] - top_left[0],
+            bottom_right[1] - top_left[1],
+        ]
+    }
+
+    fn write(&self, gfx: &Graphics) {
+        gfx.queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[Self::uniform(
+                self.scale,
+                self.translation,
+            )]),
+        );
+    }
+
+    fn uniform(scale: [f32; 2], translation: [f32; 2]) -> CameraUniform {
+        CameraUniform {
+            view_proj: [
+                [scale[0], 0.0, 0.0, 0.0],
+                [0.0, scale[1], 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [translation[0], translation[1], 0.0, 1.0],
+            ],
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+// The snake flashes for this long before it starts dissolving.
+const FLASH_TIME: f32 = 0.6;
+// Time between the snake flashing on and off.
+const FLASH_PERIOD: f32 = 0.1;
+// Time taken to dissolve the whole snake, shorter snakes dissolve slower
+// up to `MAX_SEGMENT_TIME` per segment.
+const DISSOLVE_TIME: f32 = 1.0;
+const MAX_SEGMENT_TIME: f32 = 0.08;
+// Pause on the empty board before the game is over.
+const HOLD_TIME: f32 = 0.4;
+// Strongest shake in tiles, fading out over `SHAKE_TIME`.
+const SHAKE_STRENGTH: f32 = 0.3;
+const SHAKE_TIME: f32 = 0.4;
+
+/// What the death animation shows in a single frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeathFrame {
+    // Whether the snake is drawn in its flash color.
+    pub flash: bool,
+    // Number of segments to dissolve in this frame, from the tail.
+    pub dissolve: usize,
+    // Offset of the camera in tiles.
+    pub shake: [f32; 2],
+    // Set in the frame the animation ends in.
+    pub finished: bool,
+}
+
+/// Sequence played after the snake dies: the snake flashes, then its
+/// segments dissolve one by one from the tail to the head, while
+/// the screen shakes.
+///
+/// Runs on the frame time, the simulation is stopped while it plays.
+#[derive(Debug)]
+pub struct DeathAnimation {
+    elapsed: f32,
+    // Segments including the head.
+    length: usize,
+    dissolved: usize,
+    segment_time: f32,
+    finished: bool,
+}
+
+impl DeathAnimation {
+    pub fn new(length: usize) -> Self {
+        Self {
+            elapsed: 0.0,
+            length,
+            dissolved: 0,
+            segment_time: (DISSOLVE_TIME / length as f32).min(MAX_SEGMENT_TIME),
+            finished: false,
+        }
+    }
+
+    /// Advances the animation by `frame_time`.
+    pub fn advance(&mut self, frame_time: Duration) -> DeathFrame {
+        self.elapsed += frame_time.as_secs_f32();
+        let t = self.elapsed;
+
+        let flash = t < FLASH_TIME && (t / FLASH_PERIOD) as u32 % 2 == 0;
+
+        let target = if t < FLASH_TIME {
+            0
+        } else {
+            ((t - FLASH_TIME) / self.segment_time) as usize + 1
+        };
+        let target = target.min(self.length);
+        let dissolve = target - self.dissolved;
+        self.dissolved = target;
+
+        let strength = SHAKE_STRENGTH * (1.0 - t / SHAKE_TIME).max(0.0);
+        let shake = if strength > 0.0 {
+            let mut rng = rand::thread_rng();
+            [
+                rng.gen_range(-strength..=strength),
+                rng.gen_range(-strength..=strength),
+            ]
+        } else {
+            [0.0; 2]
+        };
+
+        let end =
+            FLASH_TIME + self.segment_time * self.length as f32 + HOLD_TIME;
+        let finished = !self.finished && t >= end;
+        self.finished |= finished;
+
+        DeathFrame {
+            flash,
+            dissolve,
+            shake,
+            finished,
+        }
+    }
+}
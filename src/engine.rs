@@ -1,22 +1,104 @@
+use std::time::{Duration, Instant};
+
+use winit::event::VirtualKeyCode;
+
 use crate::{
-    graphics::Renderable, input::InputManager, game::Game, Graphics,
+    camera::Viewport,
+    game::{Game, GameOver},
+    graphics::Renderable,
+    hud::Hud,
+    input::InputManager,
+    menu::{Menu, MenuAction},
+    postprocess::PostProcess,
+    scores::{HighScore, HighScores},
+    settings::Settings,
+    shaders::ShaderWatcher,
+    theme::Theme,
+    transition::{Transition, TransitionKind, TransitionRenderer},
+    Graphics,
 };
 
+// Upper bound of ticks simulated in a single frame after a stall.
+const MAX_CATCH_UP_TICKS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Menu,
+    Playing,
+}
+
+/// Change of the state made by a transition once the screen is covered.
+#[derive(Debug, Clone, Copy)]
+enum StateChange {
+    // Starts playing, in a new game or the paused one.
+    Play { new_game: bool },
+    Pause,
+    GameOver(GameOver),
+}
+
 pub struct Engine {
     pub input: InputManager,
     game: Game,
+    hud: Hud,
+    menu: Menu,
+    post_process: PostProcess,
+    transition_renderer: TransitionRenderer,
+    // Only watches the shader files in hot reload mode.
+    shader_watcher: Option<ShaderWatcher>,
+    // Size the game is drawn at, changing with the window and retro mode.
+    viewport: Viewport,
+    settings: Settings,
+    // Built-in themes and the ones from the themes file.
+    themes: Vec<Theme>,
+    // Selected theme adjusted for the color mode.
+    theme: Theme,
+    high_scores: HighScores,
 
-    tick_counter: u32,
+    state: State,
+    // Plays while switching states, input is ignored until it finishes.
+    transition: Option<Transition<StateChange>>,
+    // Whether there is a game which can be resumed from the menu.
+    game_started: bool,
+    exit_requested: bool,
+
+    last_update: Instant,
+    // Time elapsed since the last simulation tick.
+    tick_accumulator: Duration,
+    // Start of the last frame, for animating transitions.
+    last_frame: Instant,
 }
 
 impl Engine {
     pub fn new(gfx: &Graphics) -> Self {
         let input = InputManager::init();
-        let game = Game::new(gfx);
+        let settings = Settings::default();
+        let themes = Theme::load_all();
+        let theme = themes[settings.theme]
+            .with_color_mode(settings.accessibility.color_mode);
+        let viewport = Viewport::new(gfx, &settings);
+        let game = Game::new(gfx, settings.rules, &theme, viewport);
+        let hud = Hud::new(gfx, &theme);
+        let menu = Menu::new(gfx, &theme);
         Self {
             input,
             game,
-            tick_counter: 0,
+            hud,
+            menu,
+            post_process: PostProcess::new(gfx, viewport),
+            transition_renderer: TransitionRenderer::new(gfx),
+            shader_watcher: ShaderWatcher::new(),
+            viewport,
+            settings,
+            themes,
+            theme,
+            high_scores: HighScores::load(),
+            state: State::Menu,
+            transition: None,
+            game_started: false,
+            exit_requested: false,
+            last_update: Instant::now(),
+            tick_accumulator: Duration::ZERO,
+            last_frame: Instant::now(),
         }
     }
 
@@ -30,6 +112,15 @@ impl Engine {
             },
         );
 
+        // Without effects or scaling the game is drawn straight into
+        // the window.
+        let chain = PostProcess::chain(&self.settings.effects);
+        let scene_view = if self.post_process.is_offscreen(&chain) {
+            self.post_process.scene_view()
+        } else {
+            view
+        };
+
         //Main Render Pass, render all main objects (snake, map, ...)
         {
             let mut rpass =
@@ -37,25 +128,73 @@ impl Engine {
                     label: Some("Main Render Pass"),
                     color_attachments: &[Some(
                         wgpu::RenderPassColorAttachment {
-                            view,
+                            view: scene_view,
                             resolve_target: None,
                             ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color {
-                                    r: 0.627451,
-                                    g: 0.909804,
-                                    b: 0.125490,
-                                    a: 1.,
-                                }),
+                                load: wgpu::LoadOp::Clear(
+                                    self.theme.clear_color(),
+                                ),
                                 store: true,
                             },
                         },
                     )],
-                    depth_stencil_attachment: None,
+                    depth_stencil_attachment: Some(
+                        self.post_process.depth_attachment(),
+                    ),
                 });
 
             self.game.render(&mut rpass);
         }
 
+        self.post_process.apply(
+            gfx,
+            &mut encoder,
+            view,
+            &chain,
+            !self.settings.accessibility.reduced_motion,
+        );
+
+        // The UI is drawn over the effects, keeping it sharp and in place.
+        {
+            let mut rpass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("UI Render Pass"),
+                    color_attachments: &[Some(
+                        wgpu::RenderPassColorAttachment {
+                            view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: true,
+                            },
+                        },
+                    )],
+                    depth_stencil_attachment: Some(gfx.depth_attachment()),
+                });
+
+            match self.state {
+                State::Menu => self.menu.render(&mut rpass),
+                State::Playing => {
+                    if self.settings.show_minimap {
+                        self.game.minimap().render(&mut rpass);
+                    }
+                    if self.settings.show_hud {
+                        self.hud.render(&mut rpass);
+                    }
+                }
+            }
+        }
+
+        if let Some(transition) = &self.transition {
+            self.transition_renderer.draw(
+                gfx,
+                &mut encoder,
+                view,
+                transition,
+                self.game.head_on_screen(),
+            );
+        }
+
         gfx.queue.submit(Some(encoder.finish()));
         frame.present();
 
@@ -63,25 +202,237 @@ impl Engine {
     }
 
     pub fn update(&mut self, gfx: &Graphics) {
-        self.process_input();
+        self.update_viewport(gfx);
+        self.reload_shaders(gfx);
+        let now = Instant::now();
+        let frame_time = now - self.last_frame;
+        self.last_frame = now;
+
+        if self.transition.is_some() {
+            self.update_transition(gfx, frame_time);
+        } else {
+            match self.state {
+                State::Menu => self.update_menu(gfx),
+                State::Playing => self.update_game(gfx),
+            }
+        }
+
+        self.input.reset();
+    }
 
-        self.tick_counter += 1;
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested
+    }
 
-        if self.tick_counter >= 10 {
-            self.game.update(gfx);
-            self.tick_counter = 0;
+    fn update_menu(&mut self, gfx: &Graphics) {
+        let previous_colors =
+            (self.settings.theme, self.settings.accessibility.color_mode);
+        let previous_grid = self.settings.grid;
+        let action = self.menu.update(
+            gfx,
+            Some(&self.input),
+            &mut self.settings,
+            &self.high_scores,
+            &self.themes,
+            self.game_started,
+        );
+
+        // The colors and the grid are switched right away, so they can be
+        // previewed from the menu.
+        let colors =
+            (self.settings.theme, self.settings.accessibility.color_mode);
+        if colors != previous_colors {
+            self.theme = self.themes[self.settings.theme]
+                .with_color_mode(self.settings.accessibility.color_mode);
+            self.game.apply_settings(gfx, &self.settings, &self.theme);
+            self.hud.set_theme(gfx, &self.theme);
+            self.menu.set_theme(gfx, &self.theme);
+        } else if self.settings.grid != previous_grid {
+            self.game
+                .set_grid_style(gfx, &self.settings.grid, &self.theme);
+        }
+
+        match action {
+            Some(MenuAction::NewGame) => self.start_transition(
+                TransitionKind::Iris,
+                StateChange::Play { new_game: true },
+            ),
+            Some(MenuAction::Resume) => self.start_transition(
+                TransitionKind::Iris,
+                StateChange::Play { new_game: false },
+            ),
+            Some(MenuAction::Quit) => self.exit_requested = true,
+            None => (),
+        }
+    }
+
+    /// Covers the screen with `kind`, then switches the state with
+    /// `change`. Reduced motion replaces all kinds with a fade.
+    fn start_transition(&mut self, kind: TransitionKind, change: StateChange) {
+        let kind = if self.settings.accessibility.reduced_motion {
+            TransitionKind::Fade
+        } else {
+            kind
+        };
+        self.transition = Some(Transition::new(kind, change));
+    }
+
+    /// Advances the transition, keeping the states as they are apart from
+    /// the change made once the screen is covered.
+    fn update_transition(&mut self, gfx: &Graphics, frame_time: Duration) {
+        let Some(transition) = &mut self.transition else {
+            return;
+        };
+        let change = transition.advance(frame_time);
+        let finished = transition.is_finished();
+        if let Some(change) = change {
+            self.change_state(gfx, change);
         }
+        if finished {
+            self.transition = None;
+            // The game stood still during the transition.
+            self.last_update = Instant::now();
+            self.tick_accumulator = Duration::ZERO;
+        }
+
+        // The menu is laid out without input, so it's ignored but
+        // the screen opened by the change is shown.
+        if self.state == State::Menu {
+            self.menu.update(
+                gfx,
+                None,
+                &mut self.settings,
+                &self.high_scores,
+                &self.themes,
+                self.game_started,
+            );
+        }
+    }
+
+    fn change_state(&mut self, gfx: &Graphics, change: StateChange) {
+        match change {
+            StateChange::Play { new_game } => {
+                if new_game {
+                    self.game = Game::new(
+                        gfx,
+                        self.settings.rules,
+                        &self.theme,
+                        self.viewport,
+                    );
+                    self.game_started = true;
+                }
+                self.start_playing(gfx);
+            }
+            StateChange::Pause => {
+                self.state = State::Menu;
+                self.menu.open();
+            }
+            StateChange::GameOver(game_over) => self.end_game(game_over),
+        }
+    }
+
+    fn start_playing(&mut self, gfx: &Graphics) {
+        self.state = State::Playing;
+        self.game.apply_settings(gfx, &self.settings, &self.theme);
+        // Time spent in the menu shouldn't be simulated.
+        self.last_update = Instant::now();
+        self.tick_accumulator = Duration::ZERO;
     }
 
-    fn process_input(&mut self) {
-        let input = &mut self.input;
+    fn update_game(&mut self, gfx: &Graphics) {
+        // The death animation can't be paused or skipped.
+        if self.input.get_pressed_key() == Some(VirtualKeyCode::Escape)
+            && !self.game.is_dying()
+        {
+            self.start_transition(TransitionKind::Fade, StateChange::Pause);
+            return;
+        }
 
-        self.game.process_input(input);
+        self.game
+            .process_input(&self.input, &self.settings.key_bindings);
 
-        input.reset();
+        let tick_duration = Duration::from_secs_f64(
+            1.0 / self.settings.ticks_per_second as f64,
+        );
+        let now = Instant::now();
+        let frame_time = now - self.last_update;
+        self.tick_accumulator += frame_time;
+        self.tick_accumulator = self
+            .tick_accumulator
+            .min(tick_duration * MAX_CATCH_UP_TICKS);
+        self.last_update = now;
+
+        while self.tick_accumulator >= tick_duration {
+            if let Some(game_over) = self.game.update(tick_duration) {
+                self.start_transition(
+                    TransitionKind::Wipe,
+                    StateChange::GameOver(game_over),
+                );
+                return;
+            }
+            self.tick_accumulator -= tick_duration;
+        }
+
+        // Fraction of the current tick which has elapsed.
+        let progress =
+            self.tick_accumulator.as_secs_f32() / tick_duration.as_secs_f32();
+        if let Some(game_over) = self.game.animate(gfx, progress, frame_time) {
+            self.start_transition(
+                TransitionKind::Wipe,
+                StateChange::GameOver(game_over),
+            );
+            return;
+        }
+
+        self.hud.update(
+            gfx,
+            &self.game.stats(),
+            self.settings.ticks_per_second,
+            self.high_scores.best(),
+        );
+    }
+
+    /// Records the result and opens the game over screen.
+    fn end_game(&mut self, game_over: GameOver) {
+        self.high_scores.submit(HighScore {
+            score: game_over.score,
+            time: game_over.time,
+            won: game_over.won(),
+        });
+        // The game is over, there is nothing left to resume.
+        self.game_started = false;
+        self.state = State::Menu;
+        self.menu.show_game_over(game_over);
+    }
+
+    /// Rebuilds the pipelines whose shader files changed on disk.
+    fn reload_shaders(&mut self, gfx: &Graphics) {
+        let Some(watcher) = &mut self.shader_watcher else {
+            return;
+        };
+        let changed = watcher.poll();
+        if changed.is_empty() {
+            return;
+        }
+        self.game.reload_shaders(gfx, &changed);
+        self.hud.reload_shaders(gfx, &changed);
+        self.menu.reload_shaders(gfx, &changed);
+        self.post_process.reload_shaders(gfx, &changed);
+        self.transition_renderer.reload_shaders(gfx, &changed);
     }
 
     pub fn on_resize(&mut self, gfx: &Graphics) {
-        self.game.on_resize(gfx);
+        self.update_viewport(gfx);
+    }
+
+    /// Resizes the game targets if the window size, the view size or
+    /// retro mode changed.
+    fn update_viewport(&mut self, gfx: &Graphics) {
+        let viewport = Viewport::new(gfx, &self.settings);
+        if viewport != self.viewport {
+            self.viewport = viewport;
+            self.game.set_viewport(gfx, viewport);
+            self.post_process.set_viewport(gfx, viewport);
+        }
     }
 }
@@ -1,8 +1,21 @@
+use std::time::Duration;
+
 use crate::{
-    game_elements::{AppleGen, Snake, Position},
+    batch::QuadBatch,
+    camera::{Camera, Viewport},
+    death::DeathAnimation,
+    game_elements::{AppleGen, Position, Snake, SnakeEvent},
     graphics::{Graphics, Quad, Renderable},
-    input::InputManager,
-    map::Map,
+    input::{InputManager, KeyBindings},
+    map::{BoardFilled, Map},
+    minimap::Minimap,
+    particles::{
+        Burst, ParticleSystem, DEATH_SCATTER, EAT_BURST, SPAWN_SPARKLE,
+    },
+    settings::{GridStyle, Rules, Settings, DEFAULT_VIEW_SIZE},
+    shaders::ChangedShaders,
+    texture::{Atlas, Texture},
+    theme::{Palette, Theme},
 };
 
 pub const MAP_SIZE: usize = 15;
@@ -12,6 +25,8 @@ pub const STARTING_POS: Position = Position {
 };
 pub const STARTING_SNAKE_SIZE: usize = 3;
 pub const APPLE_WORTH: usize = 5;
+// How fast the camera catches up with the head, per second.
+const CAMERA_FOLLOW_SPEED: f32 = 5.0;
 
 pub struct Game {
     snake: Snake,
@@ -19,126 +34,435 @@ pub struct Game {
     map: Map,
 
     pipeline: wgpu::RenderPipeline,
-    /*bind_group: wgpu::BindGroup,
-    bind_group_layout: wgpu::BindGroupLayout*/
+    camera: Camera,
+    // World position the camera is centered on.
+    camera_center: [f32; 2],
+    // Number of tiles visible across the shorter side of the window.
+    view_size: u32,
+    // Size the game is drawn at.
+    viewport: Viewport,
+    // Map bounds in pixels, used to clip elements to the map.
+    scissor_rect: [u32; 4],
+    atlas: Atlas,
+    palette: Palette,
+    // Quads of every game element, drawn layer by layer.
+    batch: QuadBatch,
+    minimap: Minimap,
+    particles: ParticleSystem,
+    // Set when the tiles changed since the minimap was last redrawn.
+    minimap_dirty: bool,
+    // Snaps the snake and the camera to the tiles instead of animating
+    // their movement.
+    reduced_motion: bool,
+    screen_shake: bool,
+    // Plays once the snake died, the game doesn't advance anymore.
+    death: Option<DeathAnimation>,
+    // Offset of the camera shaking in the death animation.
+    shake: [f32; 2],
+
+    // Length which wins the game, none for filling the whole board.
+    target_length: Option<usize>,
+    score: usize,
+    // Simulated time of the current game.
+    time: Duration,
 }
 
 impl Game {
-    pub fn new(gfx: &Graphics) -> Self {
-        let map = Map::new(gfx);
-        let snake = Snake::new(gfx);
-        let apple = AppleGen::new(gfx);
-
-        apple.update_mesh(gfx, map.offsets);
-
-        let shader_module = gfx
-            .device
-            .create_shader_module(wgpu::include_wgsl!("shaders/quad.wgsl"));
-
-        let layout = gfx.device.create_pipeline_layout(
-            &wgpu::PipelineLayoutDescriptor {
-                label: Some("World Render Pipeline Layout"),
-                bind_group_layouts: &[],
-                push_constant_ranges: &[],
-            },
-        );
+    pub fn new(
+        gfx: &Graphics,
+        rules: Rules,
+        theme: &Theme,
+        viewport: Viewport,
+    ) -> Self {
+        let camera_layout = Camera::bind_group_layout(gfx);
+        let camera = Camera::new(gfx, &camera_layout);
 
-        let pipeline = gfx.device.create_render_pipeline(
-            &wgpu::RenderPipelineDescriptor {
-                label: Some("World Render Pipeline"),
-                layout: Some(&layout),
-                vertex: wgpu::VertexState {
-                    module: &shader_module,
-                    entry_point: "vs_main",
-                    buffers: &[Quad::vertex_buffer_layout()],
-                },
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleStrip,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Cw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    unclipped_depth: false,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader_module,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: gfx.config.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                multiview: None,
-            },
+        let mut map = Map::new(
+            gfx,
+            &camera_layout,
+            rules.map_size,
+            &GridStyle::default(),
+            theme,
         );
+        let mut batch = QuadBatch::new(gfx, "Game Quad Buffer");
+        let mut snake = Snake::new(&mut batch, rules);
+        let mut apple = AppleGen::new(&mut batch);
+        map.board.update_tiles_data(snake.update_tile_data());
+        // The board is empty apart from the snake, so there is room for
+        // the apple. It's placed before the first tick, so the minimap
+        // never draws it off the board.
+        let _ = apple.update(&mut batch, &map);
+        // Allocated last, so the particles are drawn over the other
+        // elements of the effects layer.
+        let particles = ParticleSystem::new(&mut batch);
 
+        snake.animate(&mut batch, 0.0);
+        batch.upload(gfx);
 
+        let texture_layout = Texture::bind_group_layout(gfx);
+        let atlas = Atlas::new(gfx, &texture_layout);
+        let palette_layout = Palette::bind_group_layout(gfx);
+        let palette = Palette::new(gfx, &palette_layout, theme);
+        let pipeline = Quad::create_pipeline(
+            gfx,
+            &camera_layout,
+            &texture_layout,
+            &palette_layout,
+        );
+        let minimap = Minimap::new(
+            gfx,
+            &camera_layout,
+            &texture_layout,
+            &palette_layout,
+            rules.map_size,
+            theme,
+        );
 
-        /*let size = wgpu::Extent3d {
-            width: todo!(),
-            height: todo!(),
-            depth_or_array_layers: 1,
-        };
-
-        let texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Game Texture"),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING,
-        });
-
-        let bind_group = gfx.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Texture Bind Group Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture { sample_type: (), view_dimension: (), multisampled: () },
-                    count: todo!(),
-                }
-            ],
-        })*/
-
-        Self {
+        let mut game = Self {
             snake,
             apple,
             map,
             pipeline,
+            camera,
+            camera_center: [0.0, 0.0],
+            view_size: DEFAULT_VIEW_SIZE,
+            viewport,
+            scissor_rect: [0, 0, 0, 0],
+            atlas,
+            palette,
+            batch,
+            minimap,
+            particles,
+            minimap_dirty: true,
+            reduced_motion: false,
+            screen_shake: true,
+            death: None,
+            shake: [0.0; 2],
+            target_length: rules.target_length,
+            score: 0,
+            time: Duration::ZERO,
+        };
+        game.follow_head(gfx, None);
+        game
+    }
+
+    /// Advances the game by one tick lasting `tick`. Returns the result
+    /// if the snake reached the target length or filled the whole board.
+    /// The death of the snake starts the death animation, see `animate`.
+    pub fn update(&mut self, tick: Duration) -> Option<GameOver> {
+        if self.death.is_some() {
+            return None;
+        }
+        self.time += tick;
+        let mut outcome = None;
+        let apple = self.apple.pos;
+        match self
+            .snake
+            .update(&mut self.batch, &mut self.apple, &mut self.map)
+        {
+            Some(SnakeEvent::AteApple) => {
+                self.score += 1;
+                self.emit_particles(&EAT_BURST, &[apple]);
+            }
+            Some(SnakeEvent::Died) => {
+                self.death = Some(DeathAnimation::new(self.snake.length()));
+                return None;
+            }
+            None => (),
         }
+        self.map
+            .board
+            .update_tiles_data(self.snake.update_tile_data());
+        match self.apple.update(&mut self.batch, &self.map) {
+            Ok(Some(spawned)) => {
+                self.emit_particles(&SPAWN_SPARKLE, &[spawned])
+            }
+            Ok(None) => (),
+            Err(BoardFilled) => outcome = Some(Outcome::FilledBoard),
+        }
+        if outcome.is_none()
+            && self
+                .target_length
+                .is_some_and(|target| self.snake.length() >= target)
+        {
+            outcome = Some(Outcome::ReachedTarget);
+        }
+        self.minimap_dirty = true;
+        //println!("{}", self.map.board);
+
+        // The final board stays in view until the game over screen,
+        // the next game is a new `Game`.
+        let outcome = outcome?;
+        Some(GameOver {
+            score: self.score,
+            time: self.time,
+            outcome,
+        })
     }
 
-    pub fn update(&mut self, gfx: &Graphics) {
-        self.snake.update(gfx, &mut self.apple, &mut self.map);
-        self.map.update_tiles_data(self.snake.update_tile_data());
-        self.apple.update(gfx, &self.map);
-        //println!("{}", self.map);
+    /// Updates the meshes and the camera in between simulation ticks,
+    /// `progress` being the elapsed fraction of the current tick and
+    /// `frame_time` the time since the last frame. Returns the result
+    /// once the death animation is over.
+    pub fn animate(
+        &mut self,
+        gfx: &Graphics,
+        progress: f32,
+        frame_time: Duration,
+    ) -> Option<GameOver> {
+        let game_over = self.animate_death(frame_time);
+
+        if self.reduced_motion || self.death.is_some() {
+            self.snake.animate(&mut self.batch, 1.0);
+        } else {
+            self.snake.animate(&mut self.batch, progress);
+        }
+        if self.reduced_motion {
+            self.batch.upload(gfx);
+            self.follow_head(gfx, None);
+        } else {
+            self.particles.update(&mut self.batch, frame_time);
+            self.batch.upload(gfx);
+            self.follow_head(gfx, Some(frame_time));
+        }
+
+        if self.minimap_dirty {
+            self.minimap_dirty = false;
+            self.minimap.update_tiles(
+                gfx,
+                &self.map,
+                self.snake.head_pos(),
+                self.apple.pos,
+            );
+        }
+        game_over
+    }
+
+    /// Whether the snake died and the death animation is playing.
+    pub fn is_dying(&self) -> bool {
+        self.death.is_some()
+    }
+
+    /// Returns the center of the head in pixels from the top left corner
+    /// of the window.
+    pub fn head_on_screen(&self) -> [f32; 2] {
+        let [x, y] = self.camera.to_pixels(self.snake.head_center());
+        let scale = self.viewport.scale as f32;
+        [
+            self.viewport.offset[0] as f32 + x * scale,
+            self.viewport.offset[1] as f32 + y * scale,
+        ]
     }
 
-    pub fn on_resize(&mut self, gfx: &Graphics) {
-        self.map.resize_map(gfx);
+    /// Advances the death animation, flashing and dissolving the snake.
+    fn animate_death(&mut self, frame_time: Duration) -> Option<GameOver> {
+        let frame = self.death.as_mut()?.advance(frame_time);
+        // Flashing and shaking are left out with reduced motion.
+        self.snake
+            .set_flash(&mut self.batch, frame.flash && !self.reduced_motion);
+        for _ in 0..frame.dissolve {
+            if let Some(tile) = self.snake.dissolve_tail(&mut self.batch) {
+                self.emit_particles(&DEATH_SCATTER, &[tile]);
+            }
+        }
+        self.shake = if self.screen_shake && !self.reduced_motion {
+            frame.shake
+        } else {
+            [0.0; 2]
+        };
+
+        frame.finished.then_some(GameOver {
+            score: self.score,
+            time: self.time,
+            outcome: Outcome::Died,
+        })
+    }
+
+    /// Overview of the map, drawn on top of the game by the engine
+    /// outside of the map clipping.
+    pub fn minimap(&self) -> &Minimap {
+        &self.minimap
     }
 
-    pub fn process_input(&mut self, input: &InputManager) {
-        self.snake.process_input(input);
+    /// Changes the look of the grid, previewed from the menu.
+    pub fn set_grid_style(
+        &self,
+        gfx: &Graphics,
+        style: &GridStyle,
+        theme: &Theme,
+    ) {
+        self.map.set_grid_style(gfx, style, theme);
     }
+
+    /// Applies the display settings and the theme, which can change
+    /// during the game.
+    pub fn apply_settings(
+        &mut self,
+        gfx: &Graphics,
+        settings: &Settings,
+        theme: &Theme,
+    ) {
+        self.view_size = settings.view_size;
+        self.map.set_grid_style(gfx, &settings.grid, theme);
+        self.palette.set_theme(gfx, theme);
+        self.minimap.set_theme(gfx, theme);
+        self.minimap_dirty = true;
+
+        let accessibility = settings.accessibility;
+        self.reduced_motion = accessibility.reduced_motion;
+        self.screen_shake = settings.screen_shake;
+        if self.reduced_motion {
+            self.particles.clear(&mut self.batch);
+        }
+        self.snake
+            .set_shape_cues(&mut self.batch, accessibility.shape_cues);
+        self.apple
+            .set_shape_cues(&mut self.batch, accessibility.shape_cues);
+        self.batch.upload(gfx);
+    }
+
+    /// Throws out the particles of `burst` from the center of every tile
+    /// in `tiles`. Particles are left out with reduced motion.
+    fn emit_particles(&mut self, burst: &'static Burst, tiles: &[Position]) {
+        if self.reduced_motion {
+            return;
+        }
+        for tile in tiles {
+            self.particles.emit(burst, tile.center());
+        }
+    }
+
+    pub fn stats(&self) -> GameStats {
+        GameStats {
+            score: self.score,
+            length: self.snake.length(),
+            time: self.time,
+        }
+    }
+
+    /// Changes the size the game is drawn at, when the window is resized
+    /// or retro mode is toggled.
+    pub fn set_viewport(&mut self, gfx: &Graphics, viewport: Viewport) {
+        self.viewport = viewport;
+        self.follow_head(gfx, None);
+    }
+
+    /// Rebuilds the pipelines of the game whose shaders changed.
+    pub fn reload_shaders(&mut self, gfx: &Graphics, changed: &ChangedShaders) {
+        Quad::reload_pipeline(gfx, &mut self.pipeline, changed);
+        self.map.reload_shaders(gfx, changed);
+        self.minimap.reload_shaders(gfx, changed);
+    }
+
+    /// Moves the camera towards the head of the snake, clamped so it
+    /// doesn't show past the map edges. Maps smaller than the view are
+    /// centered. Without `frame_time` the camera jumps straight to the head.
+    fn follow_head(&mut self, gfx: &Graphics, frame_time: Option<Duration>) {
+        let (min, max) = self.map.bounds();
+        let win_size = self.viewport.size.map(|pixels| pixels as f32);
+        let pixels_per_unit =
+            self.viewport.pixels_per_unit.unwrap_or_else(|| {
+                let visible_tiles =
+                    (self.view_size as f32).min(max[0] - min[0]);
+                win_size[0].min(win_size[1]) / visible_tiles
+            });
+        let head = self.snake.head_center();
+
+        for axis in 0..2 {
+            let half_view = win_size[axis] / pixels_per_unit / 2.0;
+            let target = if half_view * 2.0 >= max[axis] - min[axis] {
+                (min[axis] + max[axis]) / 2.0
+            } else {
+                head[axis].clamp(min[axis] + half_view, max[axis] - half_view)
+            };
+            let distance = target - self.camera_center[axis];
+            self.camera_center[axis] = match frame_time {
+                // Jumps, like the head wrapping around the map, aren't
+                // smoothed.
+                Some(time) if distance.abs() < half_view => {
+                    let smoothing =
+                        1.0 - (-time.as_secs_f32() * CAMERA_FOLLOW_SPEED).exp();
+                    self.camera_center[axis] + distance * smoothing
+                }
+                _ => target,
+            };
+        }
+
+        let center =
+            [0, 1].map(|axis| self.camera_center[axis] + self.shake[axis]);
+        // With a fixed tile size the tile edges are kept on whole pixels,
+        // so the tiles don't change their shape while the camera moves.
+        let center = match self.viewport.pixels_per_unit {
+            Some(_) => [0, 1].map(|axis| {
+                let half_view = win_size[axis] / 2.0;
+                let left = center[axis] * pixels_per_unit - half_view;
+                (left.round() + half_view) / pixels_per_unit
+            }),
+            None => center,
+        };
+        self.camera
+            .look_at(gfx, self.viewport.size, center, pixels_per_unit);
+        self.scissor_rect = self.camera.scissor_rect(min, max);
+        let (view_min, view_max) = self.camera.visible_rect();
+        self.minimap.update_view(gfx, view_min, view_max);
+    }
+
+    pub fn process_input(
+        &mut self,
+        input: &InputManager,
+        key_bindings: &KeyBindings,
+    ) {
+        if self.death.is_none() {
+            self.snake.process_input(input, key_bindings);
+        }
+    }
+}
+
+pub struct GameStats {
+    pub score: usize,
+    pub length: usize,
+    pub time: Duration,
 }
 
-// Map, player, UI, Text, items are all rendered in different draw calls.
+#[derive(Debug, Clone, Copy)]
+pub struct GameOver {
+    pub score: usize,
+    pub time: Duration,
+    pub outcome: Outcome,
+}
+
+impl GameOver {
+    pub fn won(&self) -> bool {
+        self.outcome != Outcome::Died
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Died,
+    // The snake grew to the target length.
+    ReachedTarget,
+    // The snake filled the whole board, a perfect game.
+    FilledBoard,
+}
+
+// The map grid is drawn with its own pipeline, all game elements share
+// a single batch. UI and text are drawn by the engine.
 
 impl Renderable for Game {
     fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        rpass.set_bind_group(0, &self.camera.bind_group, &[]);
+        // The grid is the lowest layer, so it goes first.
         self.map.render(rpass);
 
+        // Snake segments wrapping around the edges slide out of the map.
+        let [x, y, width, height] = self.scissor_rect;
+        rpass.set_scissor_rect(x, y, width, height);
+
         rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(1, &self.atlas.bind_group, &[]);
+        rpass.set_bind_group(2, &self.palette.bind_group, &[]);
 
-        self.snake.render(rpass);
-        self.apple.render(rpass);
+        self.batch.render(rpass);
     }
 }
@@ -1,106 +1,190 @@
-use rand::Rng;
+use std::{
+    collections::VecDeque,
+    f32::consts::{FRAC_PI_2, PI},
+};
 
 use crate::{
-    graphics::{Graphics, Quad, Renderable},
-    input::InputManager,
-    map::{ElementMesh, Map, MeshOffsets, Tile}, game::{MAP_SIZE, APPLE_WORTH, STARTING_POS, STARTING_SNAKE_SIZE},
+    batch::{BatchSlot, QuadBatch},
+    game::STARTING_POS,
+    graphics::{Layer, Quad, QuadShape},
+    input::{Action, InputManager, KeyBindings},
+    map::{tile_quad, BoardFilled, ElementMesh, Map},
+    settings::{Rules, Walls},
+    texture::Sprite,
 };
 
+// Body segments the snake can have before its ring has to grow.
+const INITIAL_RING_CAPACITY: usize = 32;
+// Color of the apple drawn as a circle with shape cues.
+const APPLE_CUE_COLOR: [f32; 4] = [1.0, 0.25, 0.2, 1.0];
+// Color the snake flashes in when it dies.
+const FLASH_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+
 pub struct Snake {
+    rules: Rules,
     head: Head,
-    body: Vec<Body>,
+    // Segments behind the head, starting with the one right behind it.
+    body: VecDeque<Position>,
+    // Position of the tail before the last tick.
+    previous_tail: Option<Position>,
+    // Number of segments still to be added to the tail.
+    pending_growth: usize,
     queued_direction: Option<Direction>,
-    // Option because there is no unoccupying
-    // until the snake is at it's full starting size.
+    // Tile left by the tail in the last tick, none while growing.
     last_unoccupied: Option<Position>,
-    alive_time: usize,
 
-    vertex_buffer: wgpu::Buffer,
-    vertices: u32,
+    // Elapsed fraction of the current tick, used for interpolating
+    // the head and the tail between their previous and current positions.
+    progress: f32,
+    // Marks the head with an arrow pointing in its direction.
+    shape_cues: bool,
+    // Draws the snake in the flash color.
+    flash: bool,
+    // Set once the head dissolved after all body segments.
+    head_dissolved: bool,
+
+    // Quads of all body segments except the tail.
+    ring: BodyRing,
+    // Head and tail quads, rewritten every frame.
+    slot: BatchSlot,
 }
 
 impl Snake {
-    pub fn new(gfx: &Graphics) -> Self {
-        let vertex_buffer = gfx.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Snake Vertex Buffer"),
-            size: (std::mem::size_of::<Quad>() * (MAP_SIZE * MAP_SIZE))
-                as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let body = Self::body();
+    pub fn new(batch: &mut QuadBatch, rules: Rules) -> Self {
+        let ring = BodyRing::new(batch, INITIAL_RING_CAPACITY);
+        // The head, its arrow and the tail take up two quads each while
+        // wrapping around the map edges, the tail also keeps the tile it
+        // slides to.
+        let slot = batch.allocate(7);
 
         Self {
+            rules,
             head: Head::default(),
-            body,
+            body: VecDeque::new(),
+            previous_tail: None,
+            pending_growth: rules.starting_length,
             queued_direction: None,
             last_unoccupied: None,
-            alive_time: 0,
+            progress: 0.0,
+            shape_cues: false,
+            flash: false,
+            head_dissolved: false,
 
-            vertex_buffer,
-            vertices: 0,
+            ring,
+            slot,
         }
     }
 
-    pub fn update(&mut self, gfx: &Graphics, apple: &mut AppleGen, map: &mut Map) {
-        let offsets = map.offsets;
+    pub fn update(
+        &mut self,
+        batch: &mut QuadBatch,
+        apple: &mut AppleGen,
+        map: &mut Map,
+    ) -> Option<SnakeEvent> {
+        let mut head = self.head;
         if let Some(dir) = self.queued_direction {
-            self.head.change_dir(dir);
+            head.change_dir(dir);
         }
         self.queued_direction = None;
-        self.last_unoccupied = if self.alive_time >= self.body.len() {
-            Some(self.body.last().unwrap().pos)
-        } else {
-            None
-        };
 
-        let mut last_pos = self.head.pos;
-        self.head.advance();
+        // The fatal move isn't made, leaving the snake in place for
+        // the death animation.
+        let wrapped = head.advance(self.rules.map_size as u32);
+        if map.board.is_tile_occupied(head.pos)
+            || (wrapped && self.rules.walls == Walls::Solid)
+        {
+            return Some(SnakeEvent::Died);
+        }
+
+        let neck = self.head.pos;
+        self.head = head;
+        self.previous_tail = self.body.back().copied();
 
-        for b in &mut self.body {
-            let current_pos = b.pos;
-            b.advance_to(last_pos);
-            last_pos = current_pos;
+        self.body.push_front(neck);
+        if self.body.len() >= 2 {
+            let quad = self.segment_quad(0);
+            self.ring.push_front(batch, quad);
         }
+        self.last_unoccupied = if self.pending_growth > 0 {
+            self.pending_growth -= 1;
+            None
+        } else {
+            self.ring.pop_back(batch);
+            self.body.pop_back()
+        };
 
         if self.head.pos == apple.pos {
             apple.eat();
-            self.extend(APPLE_WORTH);
-        } else if map.is_tile_occupied(self.head.pos) {
-            apple.eat();
-            map.reset();
-            self.reset();
+            self.pending_growth += self.rules.apple_worth;
+            Some(SnakeEvent::AteApple)
+        } else {
+            None
         }
+    }
 
-        self.update_mesh(gfx, offsets);
+    pub fn length(&self) -> usize {
+        self.body.len() + 1
+    }
 
-        self.alive_time += 1;
+    pub fn head_pos(&self) -> Position {
+        self.head.pos
     }
 
-    fn extend(&mut self, add: usize) {
-        let tail_pos = self.body.last().unwrap().pos;
-        for _ in 0..add {
-            self.body.push(Body {
-                pos: tail_pos,
-            });
-        }
+    /// Returns the center of the head in world units, interpolated
+    /// the same way as its quad.
+    pub fn head_center(&self) -> [f32; 2] {
+        let from = self.head.previous_pos.to_f32();
+        let to = self.head.pos.to_f32();
+        let wrapped =
+            (to[0] - from[0]).abs() > 1.0 || (to[1] - from[1]).abs() > 1.0;
+        let t = if wrapped { 1.0 } else { self.progress };
+        [
+            from[0] + (to[0] - from[0]) * t + 0.5,
+            from[1] + (to[1] - from[1]) * t - 0.5,
+        ]
     }
 
-    fn update_mesh(&mut self, gfx: &Graphics, offsets: MeshOffsets) {
-        let vertices = self.generate_mesh(offsets);
-        self.vertices = vertices.len() as u32;
+    pub fn animate(&mut self, batch: &mut QuadBatch, progress: f32) {
+        self.progress = progress;
+        let quads = self.generate_mesh();
+        batch.write(self.slot, &quads);
+    }
 
-        gfx.queue.write_buffer(
-            &self.vertex_buffer,
-            0,
-            bytemuck::cast_slice(&vertices),
-        );
+    pub fn set_shape_cues(&mut self, batch: &mut QuadBatch, shape_cues: bool) {
+        self.shape_cues = shape_cues;
+        self.animate(batch, self.progress);
     }
 
-    fn reset(&mut self) {
-        self.head.pos = STARTING_POS;
-        self.body = Self::body();
+    /// Draws the snake in the flash color, or in its own colors.
+    pub fn set_flash(&mut self, batch: &mut QuadBatch, flash: bool) {
+        if self.flash == flash {
+            return;
+        }
+        self.flash = flash;
+        let quads: Vec<Quad> = (0..self.body.len().saturating_sub(1))
+            .map(|i| self.segment_quad(i))
+            .collect();
+        self.ring.rebuild(batch, &quads);
+        self.animate(batch, self.progress);
+    }
+
+    /// Removes the last segment, the head after all body segments,
+    /// returning its position. Returns none once the whole snake is gone.
+    pub fn dissolve_tail(&mut self, batch: &mut QuadBatch) -> Option<Position> {
+        let pos = match self.body.pop_back() {
+            Some(tail) => {
+                self.ring.pop_back(batch);
+                tail
+            }
+            None if !self.head_dissolved => {
+                self.head_dissolved = true;
+                self.head.pos
+            }
+            None => return None,
+        };
+        self.previous_tail = None;
+        self.animate(batch, self.progress);
+        Some(pos)
     }
 
     pub fn update_tile_data(&self) -> TileUpdateData {
@@ -110,143 +194,217 @@ impl Snake {
         TileUpdateData { occupy, unoccupy }
     }
 
-    pub fn process_input(&mut self, input: &InputManager) {
+    pub fn process_input(
+        &mut self,
+        input: &InputManager,
+        key_bindings: &KeyBindings,
+    ) {
         if self.queued_direction.is_some() {
             return;
         }
         if let Some(key) = input.get_keyboard_state() {
-            use winit::event::VirtualKeyCode;
             if key.is_pressed() {
-                self.queued_direction = Some(match key.keycode {
-                    VirtualKeyCode::W | VirtualKeyCode::Up => Direction::Up,
-                    VirtualKeyCode::A | VirtualKeyCode::Left => Direction::Left,
-                    VirtualKeyCode::S | VirtualKeyCode::Down => Direction::Down,
-                    VirtualKeyCode::D | VirtualKeyCode::Right => {
-                        Direction::Right
-                    }
-                    _ => return,
-                })
+                self.queued_direction =
+                    Some(match key_bindings.action(key.keycode) {
+                        Some(Action::Up) => Direction::Up,
+                        Some(Action::Left) => Direction::Left,
+                        Some(Action::Down) => Direction::Down,
+                        Some(Action::Right) => Direction::Right,
+                        None => return,
+                    })
             }
         }
     }
 
-    fn body() -> Vec<Body> {
-        let mut body = Vec::new();
-        for _ in 0..STARTING_SNAKE_SIZE {
-            body.push(Body::default());
+    /// Returns the quad of a body segment, its sprite chosen from
+    /// the neighboring segments.
+    fn segment_quad(&self, index: usize) -> Quad {
+        let size = self.rules.map_size as u32;
+        let pos = self.body[index];
+        let front = match index {
+            0 => self.head.pos,
+            _ => self.body[index - 1],
+        };
+        let back = self.body.get(index + 1).copied();
+        let sprite = body_sprite(
+            pos.direction_to(front, size),
+            back.and_then(|back| pos.direction_to(back, size)),
+        );
+        let mut quad = tile_quad(pos.to_f32(), sprite, Layer::Snakes);
+        if self.flash {
+            quad.color = FLASH_COLOR;
+        }
+        quad
+    }
+
+    /// Pushes the quad of a segment interpolated from its previous position.
+    /// A segment which wrapped around the map edge is drawn twice, sliding
+    /// out of one edge and into the opposite one.
+    fn push_segment(
+        &self,
+        quads: &mut Vec<Quad>,
+        previous: Position,
+        current: Position,
+        sprite: Sprite,
+    ) {
+        let t = self.progress;
+        let size = self.rules.map_size as u32;
+        let from = previous.to_f32();
+        let to = current.to_f32();
+        let wrapped =
+            (to[0] - from[0]).abs() > 1.0 || (to[1] - from[1]).abs() > 1.0;
+
+        match previous.direction_to(current, size) {
+            Some(dir) if wrapped => {
+                let [dx, dy] = dir.offset();
+                quads.push(tile_quad(
+                    [from[0] + dx * t, from[1] + dy * t],
+                    sprite,
+                    Layer::Snakes,
+                ));
+                quads.push(tile_quad(
+                    [to[0] - dx * (1.0 - t), to[1] - dy * (1.0 - t)],
+                    sprite,
+                    Layer::Snakes,
+                ));
+            }
+            _ => quads.push(tile_quad(
+                [
+                    from[0] + (to[0] - from[0]) * t,
+                    from[1] + (to[1] - from[1]) * t,
+                ],
+                sprite,
+                Layer::Snakes,
+            )),
         }
-        body
     }
 }
 
 impl ElementMesh for Snake {
-    fn generate_mesh(&self, offsets: MeshOffsets) -> Vec<Quad> {
+    fn generate_mesh(&self) -> Vec<Quad> {
         let mut quads = Vec::new();
+        if self.head_dissolved {
+            return quads;
+        }
 
-        let global_z = 0.0;
-        let x_offset = offsets.x_offset;
-        let y_offset = offsets.y_offset;
-        let factors = offsets.calculate_factors();
-
-        let tex_top_left = [0.8, 0.7];
-        let tex_bottom_right = [0.9, 1.0];
-
-        // Head
-        let (pos_x, pos_y) = (
-            offsets.left_x
-                + self.head.pos.x_tile as f32 * factors.to_x_coord_factor,
-            offsets.bottom_y
-                + self.head.pos.y_tile as f32 * factors.to_y_coord_factor,
-        );
-        let top_left = [
-            pos_x + factors.shorten_width,
-            pos_y - factors.shorten_height,
-            global_z,
-        ];
-        let bottom_right = [
-            pos_x + x_offset - factors.shorten_width,
-            pos_y - y_offset + factors.shorten_height,
-        ];
-        let head = Quad {
-            top_left,
-            bottom_right,
-            tex_top_left,
-            tex_bottom_right,
+        let head_sprite = match self.head.direction {
+            Direction::Up => Sprite::HeadUp,
+            Direction::Down => Sprite::HeadDown,
+            Direction::Left => Sprite::HeadLeft,
+            Direction::Right => Sprite::HeadRight,
         };
-        quads.push(head);
-
-        let tex_top_left = [0.3, 0.1];
-        let tex_bottom_right = [0.1, 0.9];
-
-        for b in &self.body {
-            let (pos_x, pos_y) = (
-                offsets.left_x
-                    + b.pos.x_tile as f32 * factors.to_x_coord_factor,
-                offsets.bottom_y
-                    + b.pos.y_tile as f32 * factors.to_y_coord_factor,
+        self.push_segment(
+            &mut quads,
+            self.head.previous_pos,
+            self.head.pos,
+            head_sprite,
+        );
+        if self.shape_cues {
+            let start = quads.len();
+            self.push_segment(
+                &mut quads,
+                self.head.previous_pos,
+                self.head.pos,
+                Sprite::Arrow,
             );
-            let top_left = [
-                pos_x + factors.shorten_width,
-                pos_y - factors.shorten_height,
-                global_z,
-            ];
-            let bottom_right = [
-                pos_x + x_offset - factors.shorten_width,
-                pos_y - y_offset + factors.shorten_height,
-            ];
-            let head = Quad {
-                top_left,
-                bottom_right,
-                tex_top_left,
-                tex_bottom_right,
+            // Kept above the tail sliding over the head of a short snake.
+            for quad in &mut quads[start..] {
+                quad.top_left[2] = Layer::Effects.depth();
+                quad.rotation = self.head.direction.angle();
+            }
+        }
+
+        if let Some(&tail) = self.body.back() {
+            let size = self.rules.map_size as u32;
+            let front = match self.body.len() {
+                1 => self.head.pos,
+                len => self.body[len - 2],
             };
-            quads.push(head);
+            let previous_tail = self.previous_tail.unwrap_or(tail);
+            if previous_tail != tail {
+                // Keeps the tile the tail slides to covered.
+                let sprite = body_sprite(
+                    tail.direction_to(front, size),
+                    tail.direction_to(previous_tail, size),
+                );
+                quads.push(tile_quad(tail.to_f32(), sprite, Layer::Snakes));
+            }
+            let sprite = body_sprite(tail.direction_to(front, size), None);
+            self.push_segment(&mut quads, previous_tail, tail, sprite);
         }
 
+        if self.flash {
+            for quad in &mut quads {
+                quad.color = FLASH_COLOR;
+            }
+        }
         quads
     }
 }
 
-impl Renderable for Snake {
-    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
-        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-
-        rpass.draw(0..4, 0..self.vertices);
+/// Picks the sprite of a body segment from the directions of the segments
+/// in front of it and behind it.
+fn body_sprite(front: Option<Direction>, back: Option<Direction>) -> Sprite {
+    use Direction::*;
+    match (front, back) {
+        (Some(Up), Some(Down)) | (Some(Down), Some(Up)) => Sprite::Vertical,
+        (Some(Left), Some(Right)) | (Some(Right), Some(Left)) => {
+            Sprite::Horizontal
+        }
+        (Some(Up), Some(Left)) | (Some(Left), Some(Up)) => Sprite::CornerUpLeft,
+        (Some(Up), Some(Right)) | (Some(Right), Some(Up)) => {
+            Sprite::CornerUpRight
+        }
+        (Some(Down), Some(Left)) | (Some(Left), Some(Down)) => {
+            Sprite::CornerDownLeft
+        }
+        (Some(Down), Some(Right)) | (Some(Right), Some(Down)) => {
+            Sprite::CornerDownRight
+        }
+        // The last segment.
+        (Some(Up), _) => Sprite::TailUp,
+        (Some(Down), _) => Sprite::TailDown,
+        (Some(Left), _) => Sprite::TailLeft,
+        (Some(Right), _) => Sprite::TailRight,
+        (None, Some(dir)) => body_sprite(Some(dir), None),
+        (None, None) => Sprite::Vertical,
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct Head {
     pos: Position,
+    previous_pos: Position,
     direction: Direction,
 }
 
 impl Head {
-    fn advance(&mut self) {
-        match self.direction {
+    /// Moves the head by one tile on a map `size` tiles wide, returns
+    /// `true` if it wrapped around the map edge.
+    fn advance(&mut self, size: u32) -> bool {
+        self.previous_pos = self.pos;
+        let wrapped_pos: Position = match self.direction {
             Direction::Down => match self.pos.decrease_y_with_bound(1) {
-                Ok(_) => (),
-                Err(_) => self.pos = (self.pos.x_tile, MAP_SIZE as u32).into(),
+                Ok(_) => return false,
+                Err(_) => (self.pos.x_tile, size).into(),
+            },
+            Direction::Up => match self.pos.increase_y_with_bound(size) {
+                Ok(_) => return false,
+                Err(_) => (self.pos.x_tile, 1).into(),
             },
-            Direction::Up => {
-                match self.pos.increase_y_with_bound(MAP_SIZE as u32) {
-                    Ok(_) => (),
-                    Err(_) => self.pos = (self.pos.x_tile, 1).into(),
-                }
-            }
             Direction::Left => match self.pos.try_decrease_x() {
-                Ok(_) => (),
-                Err(_) => {
-                    self.pos = (MAP_SIZE as u32 - 1, self.pos.y_tile).into()
-                }
+                Ok(_) => return false,
+                Err(_) => (size - 1, self.pos.y_tile).into(),
             },
             Direction::Right => {
-                match self.pos.increase_x_with_bound(MAP_SIZE as u32 - 1) {
-                    Ok(_) => (),
-                    Err(_) => self.pos = (0, self.pos.y_tile).into(),
+                match self.pos.increase_x_with_bound(size - 1) {
+                    Ok(_) => return false,
+                    Err(_) => (0, self.pos.y_tile).into(),
                 }
             }
-        }
+        };
+        self.pos = wrapped_pos;
+        true
     }
 
     fn change_dir(&mut self, new_dir: Direction) {
@@ -272,28 +430,76 @@ impl Default for Head {
     fn default() -> Self {
         Self {
             pos: STARTING_POS,
+            previous_pos: STARTING_POS,
             direction: Direction::Up,
         }
     }
 }
 
-#[derive(Debug)]
-struct Body {
-    pos: Position,
+/// Body segments stored as a ring of quads in a batch slot. Every tick
+/// only the quad of the new front segment is written and the back of
+/// the ring moves, so the upload doesn't depend on the length of the snake.
+struct BodyRing {
+    slot: BatchSlot,
+    capacity: usize,
+    // Index of the front quad in the slot.
+    front: usize,
+    len: usize,
 }
 
-impl Body {
-    fn advance_to(&mut self, pos: Position) {
-        self.pos = pos;
+impl BodyRing {
+    fn new(batch: &mut QuadBatch, capacity: usize) -> Self {
+        Self {
+            slot: batch.allocate(capacity),
+            capacity,
+            front: 0,
+            len: 0,
+        }
     }
-}
 
-impl Default for Body {
-    fn default() -> Self {
-        Self { pos: STARTING_POS }
+    fn push_front(&mut self, batch: &mut QuadBatch, quad: Quad) {
+        if self.len == self.capacity {
+            // Unrolls the ring into a larger slot, the front first.
+            let slot_quads = batch.slot_quads(self.slot);
+            let quads: Vec<Quad> = std::iter::once(quad)
+                .chain(
+                    (0..self.len)
+                        .map(|i| slot_quads[(self.front + i) % self.capacity]),
+                )
+                .collect();
+            self.rebuild(batch, &quads);
+            return;
+        }
+        self.front = (self.front + self.capacity - 1) % self.capacity;
+        self.len += 1;
+        batch.write_at(self.slot, self.front, quad);
+    }
+
+    fn pop_back(&mut self, batch: &mut QuadBatch) {
+        if self.len == 0 {
+            return;
+        }
+        let back = (self.front + self.len - 1) % self.capacity;
+        self.len -= 1;
+        batch.write_at(self.slot, back, bytemuck::Zeroable::zeroed());
+    }
+
+    /// Replaces the ring with `quads`, ordered from the front.
+    fn rebuild(&mut self, batch: &mut QuadBatch, quads: &[Quad]) {
+        batch.write(self.slot, quads);
+        self.capacity = batch.capacity(self.slot);
+        self.front = 0;
+        self.len = quads.len();
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnakeEvent {
+    AteApple,
+    // The snake hit itself or a wall and was left where it was.
+    Died,
+}
+
 pub struct TileUpdateData {
     pub occupy: Position,
     pub unoccupy: Option<Position>,
@@ -302,51 +508,49 @@ pub struct TileUpdateData {
 pub struct AppleGen {
     pub pos: Position,
     is_eaten: bool,
+    // Draws the apple as a circle.
+    shape_cues: bool,
 
-    vertex_buffer: wgpu::Buffer,
+    slot: BatchSlot,
 }
 
 impl AppleGen {
-    pub fn new(gfx: &Graphics) -> AppleGen {
+    pub fn new(batch: &mut QuadBatch) -> AppleGen {
         let pos = (0, 0).into();
-
-        let vertex_buffer = gfx.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Apple Vertex Buffer"),
-            size: std::mem::size_of::<Quad>() as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let slot = batch.allocate(1);
 
         AppleGen {
             pos,
             is_eaten: true,
-            vertex_buffer,
+            shape_cues: false,
+            slot,
         }
     }
 
-    fn find_unoccupied_pos(map: &Map) -> Position {
-        let filtered: Vec<&Tile> =
-            map.tiles.iter().filter(|x| !x.is_occupied).collect();
-        let mut rand = rand::thread_rng();
-        let index = rand.gen_range(0..filtered.len());
-        filtered.get(index).unwrap().pos
+    /// Spawns a new apple if the last one was eaten, returning its
+    /// position. Fails if there is no free tile left for it.
+    pub fn update(
+        &mut self,
+        batch: &mut QuadBatch,
+        map: &Map,
+    ) -> Result<Option<Position>, BoardFilled> {
+        if !self.is_eaten {
+            return Ok(None);
+        }
+        self.pos = map.board.random_free_tile()?;
+        self.is_eaten = false;
+        self.update_mesh(batch);
+        Ok(Some(self.pos))
     }
 
-    pub fn update(&mut self, gfx: &Graphics, map: &Map) {
-        if self.is_eaten {
-            self.is_eaten = false;
-            self.pos = Self::find_unoccupied_pos(map);
-            self.update_mesh(gfx, map.offsets);
-        }
+    pub fn update_mesh(&self, batch: &mut QuadBatch) {
+        let mesh = self.generate_mesh();
+        batch.write(self.slot, &mesh);
     }
 
-    pub fn update_mesh(&self, gfx: &Graphics, offsets: MeshOffsets) {
-        let mesh = self.generate_mesh(offsets);
-        gfx.queue.write_buffer(
-            &self.vertex_buffer,
-            0,
-            bytemuck::cast_slice(&mesh),
-        );
+    pub fn set_shape_cues(&mut self, batch: &mut QuadBatch, shape_cues: bool) {
+        self.shape_cues = shape_cues;
+        self.update_mesh(batch);
     }
 
     pub fn eat(&mut self) {
@@ -355,52 +559,14 @@ impl AppleGen {
 }
 
 impl ElementMesh for AppleGen {
-    fn generate_mesh(&self, offsets: MeshOffsets) -> Vec<Quad> {
-        let mut quads = Vec::new();
-
-        let global_z = 0.0;
-        let x_offset = offsets.x_offset;
-        let y_offset = offsets.y_offset;
-        let factors = offsets.calculate_factors();
-
-        let tex_top_left = [1.0, 1.0];
-        let tex_bottom_right = [1.0, 0.0];
-
-        let (pos_x, pos_y) = (
-            offsets.left_x + self.pos.x_tile as f32 * factors.to_x_coord_factor,
-            offsets.bottom_y
-                + self.pos.y_tile as f32 * factors.to_y_coord_factor,
-        );
-
-        let top_left = [
-            pos_x + factors.shorten_width,
-            pos_y - factors.shorten_height,
-            global_z,
-        ];
-
-        let bottom_right = [
-            pos_x + x_offset - factors.shorten_width,
-            pos_y - y_offset + factors.shorten_height,
-        ];
-
-        let apple = Quad {
-            top_left,
-            bottom_right,
-            tex_top_left,
-            tex_bottom_right,
-        };
-
-        quads.push(apple);
-
-        quads
-    }
-}
-
-impl Renderable for AppleGen {
-    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
-        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-
-        rpass.draw(0..4, 0..1);
+    fn generate_mesh(&self) -> Vec<Quad> {
+        let mut quad =
+            tile_quad(self.pos.to_f32(), Sprite::Apple, Layer::Items);
+        if self.shape_cues {
+            quad.shape = QuadShape::Circle as u32;
+            quad.color = APPLE_CUE_COLOR;
+        }
+        vec![quad]
     }
 }
 
@@ -412,6 +578,28 @@ enum Direction {
     Right,
 }
 
+impl Direction {
+    /// Returns the x and y offset of a single step in this direction.
+    fn offset(self) -> [f32; 2] {
+        match self {
+            Direction::Up => [0.0, 1.0],
+            Direction::Down => [0.0, -1.0],
+            Direction::Left => [-1.0, 0.0],
+            Direction::Right => [1.0, 0.0],
+        }
+    }
+
+    /// Returns the counterclockwise rotation from up to this direction.
+    fn angle(self) -> f32 {
+        match self {
+            Direction::Up => 0.0,
+            Direction::Down => PI,
+            Direction::Left => FRAC_PI_2,
+            Direction::Right => -FRAC_PI_2,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Position {
     pub x_tile: u32,
@@ -464,6 +652,43 @@ impl Position {
     }
 }
 
+impl Position {
+    fn to_f32(self) -> [f32; 2] {
+        [self.x_tile as f32, self.y_tile as f32]
+    }
+
+    /// Returns the center of the tile in world units.
+    pub fn center(self) -> [f32; 2] {
+        [self.x_tile as f32 + 0.5, self.y_tile as f32 - 0.5]
+    }
+
+    /// Returns the direction of a neighboring position, taking the wrapping
+    /// edges of a map `size` tiles wide into account.
+    fn direction_to(self, other: Position, size: u32) -> Option<Direction> {
+        let max_x = size - 1;
+        let (x, y) = (self.x_tile, self.y_tile);
+        let (other_x, other_y) = (other.x_tile, other.y_tile);
+
+        if y == other_y {
+            if other_x == x + 1 || (x == max_x && other_x == 0) {
+                return Some(Direction::Right);
+            }
+            if other_x + 1 == x || (x == 0 && other_x == max_x) {
+                return Some(Direction::Left);
+            }
+        }
+        if x == other_x {
+            if other_y == y + 1 || (y == size && other_y == 1) {
+                return Some(Direction::Up);
+            }
+            if other_y + 1 == y || (y == 1 && other_y == size) {
+                return Some(Direction::Down);
+            }
+        }
+        None
+    }
+}
+
 impl From<(u32, u32)> for Position {
     fn from(tile: (u32, u32)) -> Self {
         Self {
@@ -472,3 +697,88 @@ impl From<(u32, u32)> for Position {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(from: (u32, u32), to: (u32, u32)) -> Option<Direction> {
+        Position::from(from).direction_to(to.into(), 10)
+    }
+
+    #[test]
+    fn direction_to_neighbors() {
+        assert_eq!(direction((4, 5), (5, 5)), Some(Direction::Right));
+        assert_eq!(direction((4, 5), (3, 5)), Some(Direction::Left));
+        assert_eq!(direction((4, 5), (4, 6)), Some(Direction::Up));
+        assert_eq!(direction((4, 5), (4, 4)), Some(Direction::Down));
+    }
+
+    #[test]
+    fn direction_to_other_tiles() {
+        assert_eq!(direction((4, 5), (4, 5)), None);
+        assert_eq!(direction((4, 5), (5, 6)), None);
+        assert_eq!(direction((4, 5), (6, 5)), None);
+        assert_eq!(direction((4, 5), (4, 3)), None);
+        // Only tiles on the opposite edge are neighbors across it.
+        assert_eq!(direction((9, 5), (1, 5)), None);
+        assert_eq!(direction((4, 10), (4, 2)), None);
+    }
+
+    #[test]
+    fn direction_across_edges() {
+        // Columns range from 0 to `size - 1`, rows from 1 to `size`.
+        assert_eq!(direction((9, 5), (0, 5)), Some(Direction::Right));
+        assert_eq!(direction((0, 5), (9, 5)), Some(Direction::Left));
+        assert_eq!(direction((4, 10), (4, 1)), Some(Direction::Up));
+        assert_eq!(direction((4, 1), (4, 10)), Some(Direction::Down));
+        // Next to the edges without crossing them.
+        assert_eq!(direction((8, 5), (9, 5)), Some(Direction::Right));
+        assert_eq!(direction((1, 5), (0, 5)), Some(Direction::Left));
+        assert_eq!(direction((4, 9), (4, 10)), Some(Direction::Up));
+        assert_eq!(direction((4, 2), (4, 1)), Some(Direction::Down));
+    }
+
+    #[test]
+    fn direction_in_map_corners() {
+        assert_eq!(direction((9, 10), (0, 10)), Some(Direction::Right));
+        assert_eq!(direction((9, 10), (9, 1)), Some(Direction::Up));
+        assert_eq!(direction((0, 1), (9, 1)), Some(Direction::Left));
+        assert_eq!(direction((0, 1), (0, 10)), Some(Direction::Down));
+    }
+
+    #[test]
+    fn corner_sprites() {
+        use Direction::*;
+        let corners = [
+            (Up, Left, Sprite::CornerUpLeft),
+            (Up, Right, Sprite::CornerUpRight),
+            (Down, Left, Sprite::CornerDownLeft),
+            (Down, Right, Sprite::CornerDownRight),
+        ];
+        for (a, b, sprite) in corners {
+            assert_eq!(body_sprite(Some(a), Some(b)), sprite);
+            assert_eq!(body_sprite(Some(b), Some(a)), sprite);
+        }
+    }
+
+    #[test]
+    fn straight_and_tail_sprites() {
+        use Direction::*;
+        assert_eq!(body_sprite(Some(Up), Some(Down)), Sprite::Vertical);
+        assert_eq!(body_sprite(Some(Left), Some(Right)), Sprite::Horizontal);
+        assert_eq!(body_sprite(Some(Up), None), Sprite::TailUp);
+        assert_eq!(body_sprite(Some(Right), None), Sprite::TailRight);
+        assert_eq!(body_sprite(None, Some(Down)), Sprite::TailDown);
+    }
+
+    #[test]
+    fn corner_sprite_across_edge() {
+        // A segment in the top right corner, entered from the left and
+        // left through the top edge.
+        let pos = Position::from((9, 10));
+        let front = pos.direction_to((9, 1).into(), 10);
+        let back = pos.direction_to((8, 10).into(), 10);
+        assert_eq!(body_sprite(front, back), Sprite::CornerUpLeft);
+    }
+}
@@ -1,9 +1,24 @@
+use std::num::NonZeroU64;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::Camera,
+    shaders::{self, ChangedShaders},
+    texture::Texture,
+    theme::Palette,
+};
+
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
 pub struct Graphics {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface: wgpu::Surface,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
+    // Depth buffer of the UI, sized to the surface.
+    pub depth_view: wgpu::TextureView,
 }
 
 impl Graphics {
@@ -45,6 +60,8 @@ impl Graphics {
             present_mode: wgpu::PresentMode::Fifo,
         };
         surface.configure(&device, &config);
+        let depth_view =
+            Self::create_depth_view(&device, [config.width, config.height]);
 
         Self {
             device,
@@ -52,6 +69,7 @@ impl Graphics {
             surface,
             config,
             size,
+            depth_view,
         }
     }
 
@@ -59,10 +77,7 @@ impl Graphics {
         &self,
     ) -> Result<(wgpu::SurfaceTexture, wgpu::TextureView), wgpu::SurfaceError>
     {
-        let frame = match self.surface.get_current_texture() {
-            Ok(f) => f,
-            Err(err) => return Err(err),
-        };
+        let frame = self.surface.get_current_texture()?;
         let view = frame
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -75,30 +90,187 @@ impl Graphics {
         self.config.width = new_size.width.max(1);
         self.config.height = new_size.height.max(1);
         self.surface.configure(&self.device, &self.config);
+        self.depth_view = Self::create_depth_view(
+            &self.device,
+            [self.config.width, self.config.height],
+        );
+    }
+
+    /// Depth attachment of the UI cleared to the background layer.
+    pub fn depth_attachment(
+        &self,
+    ) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        depth_attachment(&self.depth_view)
+    }
+
+    /// Creates a depth buffer keeping the render layers apart.
+    pub fn create_depth_view(
+        device: &wgpu::Device,
+        size: [u32; 2],
+    ) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            // Post-processing reads the layers from the depth buffer.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
     }
 }
 
-#[repr(C)]
-#[derive(bytemuck::Zeroable, bytemuck::Pod, Clone, Copy, Debug)]
-pub struct LineVertex {
-    pub pos: [f32; 2],
+/// Depth attachment of `view` cleared to the background layer.
+pub fn depth_attachment(
+    view: &wgpu::TextureView,
+) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+    wgpu::RenderPassDepthStencilAttachment {
+        view,
+        depth_ops: Some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(Layer::Background.depth()),
+            store: true,
+        }),
+        stencil_ops: None,
+    }
 }
 
-impl LineVertex {
-    pub fn vertex_buffer_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
-        use std::mem::size_of;
-        wgpu::VertexBufferLayout {
-            array_stride: size_of::<LineVertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[wgpu::VertexAttribute {
-                format: wgpu::VertexFormat::Float32x2,
-                offset: 0,
-                shader_location: 0,
-            }],
+/// Layers the game is drawn in, from the bottom up.
+///
+/// Quads keep the depth of their layer in the z component of their top
+/// left corner. Everything is submitted from the bottom layer up: the grid
+/// is drawn first and batches draw their quads layer by layer, see
+/// [`LayerFilter`]. That order is what keeps translucent pixels blending
+/// over the right layers, the depth buffer only records the layers for
+/// post-processing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layer {
+    // Depth the depth buffer is cleared to.
+    Background,
+    Grid,
+    Items,
+    Snakes,
+    // Drawn over the game, like the arrow marking the head.
+    Effects,
+    Ui,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 6] = [
+        Layer::Background,
+        Layer::Grid,
+        Layer::Items,
+        Layer::Snakes,
+        Layer::Effects,
+        Layer::Ui,
+    ];
+
+    /// Returns the depth of the layer, higher layers are closer.
+    pub fn depth(self) -> f32 {
+        1.0 - self as u32 as f32 / 8.0
+    }
+
+    /// Depth state shared by all pipelines. Draws are submitted from
+    /// the bottom layer up, so later draws win.
+    pub fn depth_stencil_state() -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+}
+
+/// Depth of the layer drawn by the quad pipeline, bound to group 3.
+///
+/// The uniform holds the depth of every layer, one after the other, and
+/// the dynamic offset selects one of them. Quads of other layers are culled
+/// in the vertex shader, which lets a batch be drawn layer by layer.
+pub struct LayerFilter {
+    bind_group: wgpu::BindGroup,
+    // Distance between the depths in the buffer, in bytes.
+    stride: u32,
+}
+
+impl LayerFilter {
+    // Size of the uniform of a layer, the depth padded to 16 bytes.
+    const SIZE: u64 = 16;
+
+    pub fn bind_group_layout(gfx: &Graphics) -> wgpu::BindGroupLayout {
+        gfx.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Layer Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: NonZeroU64::new(Self::SIZE),
+                    },
+                    count: None,
+                }],
+            })
+    }
+
+    pub fn new(gfx: &Graphics) -> Self {
+        let stride = gfx.device.limits().min_uniform_buffer_offset_alignment;
+        let mut contents = vec![0; Layer::ALL.len() * stride as usize];
+        for (layer, uniform) in Layer::ALL
+            .iter()
+            .zip(contents.chunks_exact_mut(stride as usize))
+        {
+            uniform[..4].copy_from_slice(&layer.depth().to_ne_bytes());
         }
+        let buffer =
+            gfx.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Layer Buffer"),
+                    contents: &contents,
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+        let bind_group =
+            gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Layer Bind Group"),
+                layout: &Self::bind_group_layout(gfx),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(
+                        wgpu::BufferBinding {
+                            buffer: &buffer,
+                            offset: 0,
+                            size: NonZeroU64::new(Self::SIZE),
+                        },
+                    ),
+                }],
+            });
+
+        Self { bind_group, stride }
+    }
+
+    /// Binds the filter so only the quads of `layer` are drawn.
+    pub fn select<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        layer: Layer,
+    ) {
+        let offset = layer as u32 * self.stride;
+        rpass.set_bind_group(3, &self.bind_group, &[offset]);
     }
 }
 
+/// Instance drawn by the quad pipeline, a textured rectangle or a shape
+/// cut out with a signed distance function.
+///
+/// Zeroed quads are fully transparent, which is used for hiding them.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 pub struct Quad {
@@ -106,6 +278,46 @@ pub struct Quad {
     pub bottom_right: [f32; 2],
     pub tex_top_left: [f32; 2],
     pub tex_bottom_right: [f32; 2],
+    // Index of the palette color the texture is tinted with,
+    // see `PaletteColor`.
+    pub palette: u32,
+    // Multiplies the texture, or fills the shape of untextured quads.
+    pub color: [f32; 4],
+    // Counterclockwise rotation around the center in radians, clockwise
+    // on screen where the y axis points down.
+    pub rotation: f32,
+    // Used by `QuadShape::RoundedRect`, in the units of the corners.
+    pub corner_radius: f32,
+    // See `QuadShape`.
+    pub shape: u32,
+}
+
+impl Default for Quad {
+    fn default() -> Self {
+        Self {
+            top_left: [0.0; 3],
+            bottom_right: [0.0; 2],
+            tex_top_left: [0.0; 2],
+            tex_bottom_right: [0.0; 2],
+            palette: 0,
+            color: [1.0; 4],
+            rotation: 0.0,
+            corner_radius: 0.0,
+            shape: QuadShape::Textured as u32,
+        }
+    }
+}
+
+/// What a quad is filled with. All shapes except `Textured` ignore
+/// the texture and are filled with the quad color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u32)]
+pub enum QuadShape {
+    Textured,
+    Rect,
+    RoundedRect,
+    // Circle fitting into the quad.
+    Circle,
 }
 
 impl Quad {
@@ -136,11 +348,127 @@ impl Quad {
                     offset: size_of::<[f32; 7]>() as wgpu::BufferAddress,
                     shader_location: 3,
                 },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Uint32,
+                    offset: size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32,
+                    offset: size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32,
+                    offset: size_of::<[f32; 15]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Uint32,
+                    offset: size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                },
             ],
         }
     }
 }
 
+impl Quad {
+    pub const SHADER: &'static str = "quad.wgsl";
+
+    /// Creates the pipeline which draws textured quad instances, with
+    /// the camera bound to group 0, the texture to group 1, the palette
+    /// to group 2 and the [`LayerFilter`] to group 3.
+    pub fn create_pipeline(
+        gfx: &Graphics,
+        camera_layout: &wgpu::BindGroupLayout,
+        texture_layout: &wgpu::BindGroupLayout,
+        palette_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let shader_module =
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(Self::SHADER),
+                    source: wgpu::ShaderSource::Wgsl(shaders::source(
+                        Self::SHADER,
+                    )),
+                });
+
+        let layout = gfx.device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Quad Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    camera_layout,
+                    texture_layout,
+                    palette_layout,
+                    &LayerFilter::bind_group_layout(gfx),
+                ],
+                push_constant_ranges: &[],
+            },
+        );
+
+        gfx.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Quad Render Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[Quad::vertex_buffer_layout()],
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Cw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(Layer::depth_stencil_state()),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: gfx.config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            })
+    }
+
+    /// Rebuilds `pipeline` if the quad shader changed. The pipeline is
+    /// kept if the shader fails to compile.
+    pub fn reload_pipeline(
+        gfx: &Graphics,
+        pipeline: &mut wgpu::RenderPipeline,
+        changed: &ChangedShaders,
+    ) {
+        if !changed.any(&[Self::SHADER]) {
+            return;
+        }
+        let rebuilt = shaders::try_rebuild(gfx, "Quad Render Pipeline", || {
+            Self::create_pipeline(
+                gfx,
+                &Camera::bind_group_layout(gfx),
+                &Texture::bind_group_layout(gfx),
+                &Palette::bind_group_layout(gfx),
+            )
+        });
+        if let Some(rebuilt) = rebuilt {
+            *pipeline = rebuilt;
+        }
+    }
+}
+
 pub trait Renderable {
     fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>);
 }
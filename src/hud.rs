@@ -0,0 +1,63 @@
+use crate::{
+    game::GameStats,
+    graphics::{Graphics, Renderable},
+    scores::format_time,
+    shaders::ChangedShaders,
+    text::TextRenderer,
+    theme::Theme,
+};
+
+const HUD_SCALE: f32 = 2.0;
+const HUD_MARGIN: f32 = 10.0;
+const LINE_SPACING: f32 = 1.5;
+
+/// Heads-up display with the current game statistics.
+pub struct Hud {
+    text: TextRenderer,
+}
+
+impl Hud {
+    pub fn new(gfx: &Graphics, theme: &Theme) -> Self {
+        Self {
+            text: TextRenderer::new(gfx, theme),
+        }
+    }
+
+    pub fn set_theme(&self, gfx: &Graphics, theme: &Theme) {
+        self.text.set_theme(gfx, theme);
+    }
+
+    pub fn reload_shaders(&mut self, gfx: &Graphics, changed: &ChangedShaders) {
+        self.text.reload_shaders(gfx, changed);
+    }
+
+    pub fn update(
+        &mut self,
+        gfx: &Graphics,
+        stats: &GameStats,
+        speed: u32,
+        high_score: usize,
+    ) {
+        let lines = [
+            format!("SCORE {}", stats.score),
+            format!("LENGTH {}", stats.length),
+            format!("SPEED {}", speed),
+            format!("TIME {}", format_time(stats.time)),
+            format!("BEST {}", high_score.max(stats.score)),
+        ];
+
+        let line_height = TextRenderer::measure("", HUD_SCALE)[1];
+        for (i, line) in lines.iter().enumerate() {
+            let y = HUD_MARGIN + i as f32 * line_height * LINE_SPACING;
+            self.text.queue(line, [HUD_MARGIN, y], HUD_SCALE);
+        }
+
+        self.text.prepare(gfx);
+    }
+}
+
+impl Renderable for Hud {
+    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        self.text.render(rpass);
+    }
+}
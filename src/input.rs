@@ -1,9 +1,15 @@
-use winit::event::{ElementState, VirtualKeyCode};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{ElementState, VirtualKeyCode},
+};
 
 #[derive(Debug)]
 pub struct InputManager {
     keyboard_state: Option<KeyboardState>,
     mouse_delta: MouseDelta,
+    // Cursor position in pixels, from the top left of the window.
+    cursor_pos: Option<[f32; 2]>,
+    mouse_clicked: bool,
 }
 
 impl InputManager {
@@ -14,11 +20,14 @@ impl InputManager {
         Self {
             keyboard_state,
             mouse_delta,
+            cursor_pos: None,
+            mouse_clicked: false,
         }
     }
 
     pub fn reset(&mut self) {
         self.keyboard_state = None;
+        self.mouse_clicked = false;
         self.mouse_delta.delta_x = 0;
         self.mouse_delta.delta_y = 0;
     }
@@ -32,6 +41,21 @@ impl InputManager {
         self.mouse_delta
     }
 
+    /// Returns the key pressed during this frame.
+    pub fn get_pressed_key(&self) -> Option<VirtualKeyCode> {
+        self.keyboard_state
+            .filter(|state| state.is_pressed())
+            .map(|state| state.keycode)
+    }
+
+    pub fn get_cursor_pos(&self) -> Option<[f32; 2]> {
+        self.cursor_pos
+    }
+
+    pub fn is_mouse_clicked(&self) -> bool {
+        self.mouse_clicked
+    }
+
     pub fn keyboard_input(
         &mut self,
         state: ElementState,
@@ -44,6 +68,18 @@ impl InputManager {
         self.mouse_delta.delta_x = raw_delta.0 as i32;
         self.mouse_delta.delta_y = -raw_delta.1 as i32;
     }
+
+    pub fn cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.cursor_pos = Some([position.x as f32, position.y as f32]);
+    }
+
+    pub fn cursor_left(&mut self) {
+        self.cursor_pos = None;
+    }
+
+    pub fn mouse_click(&mut self) {
+        self.mouse_clicked = true;
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -63,3 +99,71 @@ pub struct MouseDelta {
     delta_x: i32,
     delta_y: i32,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Action {
+    pub const ALL: [Action; 4] =
+        [Action::Up, Action::Down, Action::Left, Action::Right];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Up => "UP",
+            Action::Down => "DOWN",
+            Action::Left => "LEFT",
+            Action::Right => "RIGHT",
+        }
+    }
+}
+
+/// Two rebindable keys for every movement action.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyBindings {
+    pub up: [VirtualKeyCode; 2],
+    pub down: [VirtualKeyCode; 2],
+    pub left: [VirtualKeyCode; 2],
+    pub right: [VirtualKeyCode; 2],
+}
+
+impl KeyBindings {
+    pub fn action(&self, keycode: VirtualKeyCode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| self.keys(*action).contains(&keycode))
+    }
+
+    pub fn keys(&self, action: Action) -> &[VirtualKeyCode; 2] {
+        match action {
+            Action::Up => &self.up,
+            Action::Down => &self.down,
+            Action::Left => &self.left,
+            Action::Right => &self.right,
+        }
+    }
+
+    pub fn keys_mut(&mut self, action: Action) -> &mut [VirtualKeyCode; 2] {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: [VirtualKeyCode::W, VirtualKeyCode::Up],
+            down: [VirtualKeyCode::S, VirtualKeyCode::Down],
+            left: [VirtualKeyCode::A, VirtualKeyCode::Left],
+            right: [VirtualKeyCode::D, VirtualKeyCode::Right],
+        }
+    }
+}
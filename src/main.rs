@@ -1,9 +1,26 @@
+mod batch;
+mod buffer;
+mod camera;
+mod death;
 mod engine;
+mod game;
 mod game_elements;
 mod graphics;
+mod hud;
 mod input;
 mod map;
-mod game;
+mod menu;
+mod minimap;
+mod particles;
+mod postprocess;
+mod scores;
+mod settings;
+mod shaders;
+mod text;
+mod texture;
+mod theme;
+mod transition;
+mod ui;
 
 use std::time::{Duration, Instant};
 
@@ -11,7 +28,7 @@ use engine::Engine;
 use graphics::Graphics;
 use pollster::block_on;
 use winit::{
-    event::{ElementState, KeyboardInput, VirtualKeyCode},
+    event::{ElementState, KeyboardInput, MouseButton},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
@@ -38,15 +55,6 @@ fn run() {
                 winit::event::WindowEvent::CloseRequested => {
                     *control_flow = ControlFlow::Exit
                 }
-                winit::event::WindowEvent::KeyboardInput {
-                    input:
-                        KeyboardInput {
-                            state: ElementState::Pressed,
-                            virtual_keycode: Some(VirtualKeyCode::Escape),
-                            ..
-                        },
-                    ..
-                } => *control_flow = ControlFlow::Exit,
                 winit::event::WindowEvent::KeyboardInput {
                     input:
                         KeyboardInput {
@@ -56,6 +64,17 @@ fn run() {
                         },
                     ..
                 } => engine.input.keyboard_input(state, keycode),
+                winit::event::WindowEvent::CursorMoved { position, .. } => {
+                    engine.input.cursor_moved(position)
+                }
+                winit::event::WindowEvent::CursorLeft { .. } => {
+                    engine.input.cursor_left()
+                }
+                winit::event::WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } => engine.input.mouse_click(),
                 winit::event::WindowEvent::Resized(new_size)
                 | winit::event::WindowEvent::ScaleFactorChanged {
                     new_inner_size: &mut new_size,
@@ -68,6 +87,10 @@ fn run() {
             },
             winit::event::Event::RedrawRequested(_) => {
                 engine.update(&graphics);
+                if engine.exit_requested() {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
 
                 match engine.render(&graphics) {
                     Ok(_) => (),
@@ -1,48 +1,152 @@
 use std::fmt::{Display, Write};
 
+use rand::Rng;
+
 use wgpu::util::DeviceExt;
 
 use crate::{
+    camera::Camera,
     game_elements::{Position, TileUpdateData},
-    graphics::{Graphics, LineVertex, Quad, Renderable}, game::MAP_SIZE,
+    graphics::{Graphics, Layer, Quad, Renderable},
+    settings::GridStyle,
+    shaders::{self, ChangedShaders},
+    texture::Sprite,
+    theme::Theme,
 };
 
+const GRID_SHADER: &str = "grid.wgsl";
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+    line_color: [f32; 4],
+    checker_color: [f32; 4],
+    size: [f32; 2],
+    thickness: f32,
+    checkerboard: f32,
+    depth: f32,
+    _padding: [f32; 3],
+}
+
 pub struct Map {
-    // Tile array marks occupied and unoccupied tiles.
-    pub tiles: [Tile; MAP_SIZE * MAP_SIZE],
-    pub offsets: MeshOffsets,
+    pub board: Board,
 
+    // The grid is drawn procedurally over a single quad covering the map.
     pipeline: wgpu::RenderPipeline,
-    mesh: MapMesh,
+    grid_buffer: wgpu::Buffer,
+    grid_bind_group: wgpu::BindGroup,
 }
 
 impl Map {
-    pub fn new(gfx: &Graphics) -> Self {
-        let tiles = Self::generate_tiles();
+    /// Creates a square map `size` tiles wide. The grid is drawn with
+    /// the camera bound to group 0.
+    pub fn new(
+        gfx: &Graphics,
+        camera_layout: &wgpu::BindGroupLayout,
+        size: usize,
+        style: &GridStyle,
+        theme: &Theme,
+    ) -> Self {
+        let grid_buffer =
+            gfx.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Grid Buffer"),
+                    contents: bytemuck::cast_slice(&[Self::grid_uniform(
+                        size, style, theme,
+                    )]),
+                    usage: wgpu::BufferUsages::UNIFORM
+                        | wgpu::BufferUsages::COPY_DST,
+                });
+        let grid_layout = Self::grid_bind_group_layout(gfx);
+        let grid_bind_group =
+            gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Grid Bind Group"),
+                layout: &grid_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: grid_buffer.as_entire_binding(),
+                }],
+            });
+
+        let pipeline = Self::create_pipeline(gfx, camera_layout, &grid_layout);
+
+        Self {
+            board: Board::new(size),
+            pipeline,
+            grid_buffer,
+            grid_bind_group,
+        }
+    }
+
+    /// Rebuilds the grid pipeline if its shader changed. The pipeline is
+    /// kept if the shader fails to compile.
+    pub fn reload_shaders(&mut self, gfx: &Graphics, changed: &ChangedShaders) {
+        if !changed.any(&[GRID_SHADER]) {
+            return;
+        }
+        let rebuilt =
+            shaders::try_rebuild(gfx, "World Render Pipeline", || {
+                Self::create_pipeline(
+                    gfx,
+                    &Camera::bind_group_layout(gfx),
+                    &Self::grid_bind_group_layout(gfx),
+                )
+            });
+        if let Some(rebuilt) = rebuilt {
+            self.pipeline = rebuilt;
+        }
+    }
 
-        let shader_module = gfx
-            .device
-            .create_shader_module(wgpu::include_wgsl!("shaders/line.wgsl"));
+    fn grid_bind_group_layout(gfx: &Graphics) -> wgpu::BindGroupLayout {
+        gfx.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Grid Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            })
+    }
+
+    fn create_pipeline(
+        gfx: &Graphics,
+        camera_layout: &wgpu::BindGroupLayout,
+        grid_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let shader_module =
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(GRID_SHADER),
+                    source: wgpu::ShaderSource::Wgsl(shaders::source(
+                        GRID_SHADER,
+                    )),
+                });
 
         let layout = gfx.device.create_pipeline_layout(
             &wgpu::PipelineLayoutDescriptor {
                 label: Some("World Render Pipeline Layout"),
-                bind_group_layouts: &[],
+                bind_group_layouts: &[camera_layout, grid_layout],
                 push_constant_ranges: &[],
             },
         );
 
-        let pipeline = gfx.device.create_render_pipeline(
-            &wgpu::RenderPipelineDescriptor {
+        gfx.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                 label: Some("World Render Pipeline"),
                 layout: Some(&layout),
                 vertex: wgpu::VertexState {
                     module: &shader_module,
                     entry_point: "vs_main",
-                    buffers: &[LineVertex::vertex_buffer_layout()],
+                    buffers: &[],
                 },
                 primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::LineList,
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
                     strip_index_format: None,
                     front_face: wgpu::FrontFace::Cw,
                     cull_mode: Some(wgpu::Face::Back),
@@ -50,200 +154,151 @@ impl Map {
                     polygon_mode: wgpu::PolygonMode::Fill,
                     conservative: false,
                 },
-                depth_stencil: None,
+                depth_stencil: Some(Layer::depth_stencil_state()),
                 multisample: wgpu::MultisampleState::default(),
                 fragment: Some(wgpu::FragmentState {
                     module: &shader_module,
                     entry_point: "fs_main",
                     targets: &[Some(wgpu::ColorTargetState {
                         format: gfx.config.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
                 }),
                 multiview: None,
-            },
-        );
+            })
+    }
 
-        let (mesh, offsets) = Self::_new_map_mesh(gfx);
+    pub fn set_grid_style(
+        &self,
+        gfx: &Graphics,
+        style: &GridStyle,
+        theme: &Theme,
+    ) {
+        gfx.queue.write_buffer(
+            &self.grid_buffer,
+            0,
+            bytemuck::cast_slice(&[Self::grid_uniform(
+                self.board.size,
+                style,
+                theme,
+            )]),
+        );
+    }
 
-        Self {
-            tiles,
-            offsets,
-            pipeline,
-            mesh,
+    /// Lines are drawn in the grid color of the theme, faded by
+    /// the opacity of the style.
+    fn grid_uniform(
+        size: usize,
+        style: &GridStyle,
+        theme: &Theme,
+    ) -> GridUniform {
+        let [r, g, b, a] = theme.grid;
+        GridUniform {
+            line_color: [r, g, b, a * style.opacity],
+            checker_color: style.checker_color,
+            size: [size as f32, size as f32],
+            thickness: style.thickness as f32,
+            checkerboard: if style.checkerboard { 1.0 } else { 0.0 },
+            depth: Layer::Grid.depth(),
+            _padding: [0.0; 3],
         }
     }
 
-    pub fn update_tiles_data(&mut self, data: TileUpdateData) {
-        let occupy_tile_index = Self::pos_to_tile_index(data.occupy);
-        self.tiles[occupy_tile_index].is_occupied = true;
-        if let Some(unoccupy) = data.unoccupy {
-            let unoccupy_tile_index = Self::pos_to_tile_index(unoccupy);
-            self.tiles[unoccupy_tile_index].is_occupied = false;
-        }
+    /// Returns the bottom left and the top right corner of the map
+    /// in world units.
+    pub fn bounds(&self) -> ([f32; 2], [f32; 2]) {
+        ([0.0, 0.0], [self.board.size as f32, self.board.size as f32])
     }
+}
 
-    fn _new_map_mesh(gfx: &Graphics) -> (MapMesh, MeshOffsets) {
-        let mut vertices = Vec::new();
-
-        let win_width = gfx.config.width;
-        let win_height = gfx.config.height;
-
-        // Calculate bounds of the map in coordinates.
-        let (left_x, right_x, top_y, bottom_y, x_line_offset, y_line_offset) =
-            match win_width.cmp(&win_height) {
-                std::cmp::Ordering::Less => {
-                    let half_of_uncovered_height =
-                        (win_height - win_width) as f32 / 2.0;
-                    let percentage_of_the_half =
-                        half_of_uncovered_height / win_height as f32;
-                    let delta_of_uncovered_half_in_coords =
-                        percentage_of_the_half * 2.0;
-                    let top = 1.0 - delta_of_uncovered_half_in_coords;
-                    let bottom = -1.0 + delta_of_uncovered_half_in_coords;
-                    let left: f32 = -1.0;
-                    let right: f32 = 1.0;
-                    let x_line_offset = 2.0 / MAP_SIZE as f32;
-                    let y_line_offset = (top + bottom.abs()) / MAP_SIZE as f32;
-                    (left, right, top, bottom, x_line_offset, y_line_offset)
-                }
-                std::cmp::Ordering::Equal => (
-                    -1.0,
-                    1.0,
-                    1.0,
-                    -1.0,
-                    2.0 / MAP_SIZE as f32,
-                    2.0 / MAP_SIZE as f32,
-                ),
-                std::cmp::Ordering::Greater => {
-                    let half_of_uncovered_width =
-                        (win_width - win_height) as f32 / 2.0;
-                    let percentage_of_the_half =
-                        half_of_uncovered_width / win_width as f32;
-                    let delta_of_uncovered_half_in_coords =
-                        percentage_of_the_half * 2.0;
-                    let top = 1.0;
-                    let bottom = -1.0;
-                    let left: f32 = -1.0 + delta_of_uncovered_half_in_coords;
-                    let right: f32 = 1.0 - delta_of_uncovered_half_in_coords;
-                    let x_line_offset = (right + left.abs()) / MAP_SIZE as f32;
-                    let y_line_offset = 2.0 / MAP_SIZE as f32;
-                    (left, right, top, bottom, x_line_offset, y_line_offset)
-                }
-            };
+impl Renderable for Map {
+    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(1, &self.grid_bind_group, &[]);
 
-        // Vertical lines
-        for l in 0..MAP_SIZE {
-            let offset_x = l as f32 * x_line_offset;
-            vertices.push(line(left_x + offset_x, top_y));
-            vertices.push(line(left_x + offset_x, bottom_y));
-        }
+        rpass.draw(0..4, 0..1);
+    }
+}
 
-        // Last vertical line
-        vertices.push(line(right_x, top_y));
-        vertices.push(line(right_x, bottom_y));
+/// Occupied and unoccupied tiles of the map.
+pub struct Board {
+    // Width and height of the board in tiles.
+    size: usize,
+    // Tile array marks occupied and unoccupied tiles.
+    pub tiles: Vec<Tile>,
+    free_tiles: FreeTiles,
+}
 
-        // Horizontal lines
-        for l in 0..MAP_SIZE {
-            let offset_y = l as f32 * y_line_offset;
-            vertices.push(line(left_x, bottom_y + offset_y));
-            vertices.push(line(right_x, bottom_y + offset_y));
+impl Board {
+    /// Creates an empty square board `size` tiles wide.
+    pub fn new(size: usize) -> Self {
+        let tiles = Self::generate_tiles(size);
+        let free_tiles = FreeTiles::new(&tiles);
+        Self {
+            size,
+            tiles,
+            free_tiles,
         }
-
-        // Last horizontal line
-        vertices.push(line(left_x, top_y));
-        vertices.push(line(right_x, top_y));
-
-        let vertex_buffer =
-            gfx.device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Map Vertex Buffer"),
-                    contents: bytemuck::cast_slice(&vertices),
-                    usage: wgpu::BufferUsages::VERTEX,
-                });
-
-        let offsets = MeshOffsets {
-            left_x,
-            bottom_y,
-            x_offset: x_line_offset,
-            y_offset: y_line_offset,
-            map_width: left_x.abs() + right_x,
-            map_height: top_y + bottom_y.abs(),
-        };
-
-        (
-            MapMesh {
-                vertex_buffer,
-                vertices_count: vertices.len() as u32,
-            },
-            offsets,
-        )
     }
 
-    pub fn resize_map(&mut self, gfx: &Graphics) {
-        let (mesh, offsets) = Self::_new_map_mesh(gfx);
-        self.mesh = mesh;
-        self.offsets = offsets;
+    pub fn update_tiles_data(&mut self, data: TileUpdateData) {
+        let occupy_tile_index = self.pos_to_tile_index(data.occupy);
+        self.tiles[occupy_tile_index].is_occupied = true;
+        self.free_tiles.remove(occupy_tile_index);
+        if let Some(unoccupy) = data.unoccupy {
+            let unoccupy_tile_index = self.pos_to_tile_index(unoccupy);
+            self.tiles[unoccupy_tile_index].is_occupied = false;
+            self.free_tiles.insert(unoccupy_tile_index);
+        }
     }
 
     pub fn is_tile_occupied(&self, pos: Position) -> bool {
-        let index = Self::pos_to_tile_index(pos);
+        let index = self.pos_to_tile_index(pos);
         self.tiles[index].is_occupied
     }
 
-    pub fn reset(&mut self) {
-        self.tiles = Self::generate_tiles();
+    /// Picks a random unoccupied tile.
+    pub fn random_free_tile(&self) -> Result<Position, BoardFilled> {
+        let index = self.free_tiles.random().ok_or(BoardFilled)?;
+        Ok(self.tiles[index].pos)
     }
 
-    fn pos_to_tile_index<P: Into<Position>>(pos: P) -> usize {
+    fn pos_to_tile_index<P: Into<Position>>(&self, pos: P) -> usize {
         let pos = pos.into();
-        (pos.y_tile * MAP_SIZE as u32 + pos.x_tile - MAP_SIZE as u32) as usize
+        let size = self.size as u32;
+        (pos.y_tile * size + pos.x_tile - size) as usize
     }
 
-    fn generate_tiles() -> [Tile; MAP_SIZE * MAP_SIZE] {
-        let mut tiles = Vec::new();
-        for i in 1..=MAP_SIZE {
-            for j in 0..MAP_SIZE {
+    fn generate_tiles(size: usize) -> Vec<Tile> {
+        let mut tiles = Vec::with_capacity(size * size);
+        for i in 1..=size {
+            for j in 0..size {
                 tiles.push(Tile {
                     is_occupied: false,
                     pos: (j as u32, i as u32).into(),
                 })
             }
         }
-        tiles.try_into().unwrap()
-    }
-}
-
-fn line(x: f32, y: f32) -> LineVertex {
-    LineVertex { pos: [x, y] }
-}
-
-impl Renderable for Map {
-    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
-        rpass.set_pipeline(&self.pipeline);
-
-        rpass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
-
-        rpass.draw(0..self.mesh.vertices_count, 0..1);
+        tiles
     }
 }
 
-impl Display for Map {
+impl Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let tile_width = 3;
-        let width = MAP_SIZE * tile_width + 1;
-        for i in 1..=MAP_SIZE {
+        let width = self.size * tile_width + 1;
+        for i in 1..=self.size {
             f.write_str(&format!(
                 "\n{blank:->width$}\n",
                 blank = '-',
                 width = width
             ))?;
             f.write_char('|')?;
-            for j in 0..MAP_SIZE {
-                let tile = &self.tiles[Self::pos_to_tile_index((
+            for j in 0..self.size {
+                let tile = &self.tiles[self.pos_to_tile_index((
                     j as u32,
-                    MAP_SIZE as u32 + 1 - i as u32,
+                    self.size as u32 + 1 - i as u32,
                 ))];
                 if tile.is_occupied {
                     f.write_char('✅')?;
@@ -263,46 +318,186 @@ pub struct Tile {
     pub pos: Position,
 }
 
-struct MapMesh {
-    vertex_buffer: wgpu::Buffer,
-    vertices_count: u32,
-}
+/// Returned when there are no unoccupied tiles left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardFilled;
 
-#[derive(Debug, Clone, Copy)]
-pub struct MeshOffsets {
-    pub left_x: f32,
-    pub bottom_y: f32,
-    pub x_offset: f32,
-    pub y_offset: f32,
-    pub map_width: f32,
-    pub map_height: f32,
+/// Set of unoccupied tile indices supporting insertion, removal and
+/// picking a random tile in constant time.
+struct FreeTiles {
+    tiles: Vec<usize>,
+    // Index into `tiles` for every tile, none if the tile is occupied.
+    indices: Vec<Option<usize>>,
 }
 
-impl MeshOffsets {
-    pub fn calculate_factors(&self) -> MeshFactors {
-        // Factors used for turning map position (0-30) to real coordinates.
-        let to_x_coord_factor = self.map_width / MAP_SIZE as f32;
-        let to_y_coord_factor = self.map_height / MAP_SIZE as f32;
-        let shorten_by_factor = 0.05;
-        let shorten_width = shorten_by_factor * to_x_coord_factor;
-        let shorten_height = shorten_by_factor * to_y_coord_factor;
-
-        MeshFactors {
-            to_x_coord_factor,
-            to_y_coord_factor,
-            shorten_width,
-            shorten_height,
+impl FreeTiles {
+    fn new(tiles: &[Tile]) -> Self {
+        let mut free_tiles = Self {
+            tiles: Vec::with_capacity(tiles.len()),
+            indices: vec![None; tiles.len()],
+        };
+        for (index, tile) in tiles.iter().enumerate() {
+            if !tile.is_occupied {
+                free_tiles.insert(index);
+            }
+        }
+        free_tiles
+    }
+
+    fn insert(&mut self, tile_index: usize) {
+        if self.indices[tile_index].is_none() {
+            self.indices[tile_index] = Some(self.tiles.len());
+            self.tiles.push(tile_index);
+        }
+    }
+
+    fn remove(&mut self, tile_index: usize) {
+        if let Some(index) = self.indices[tile_index].take() {
+            self.tiles.swap_remove(index);
+            // The last tile took the place of the removed one.
+            if let Some(&moved) = self.tiles.get(index) {
+                self.indices[moved] = Some(index);
+            }
+        }
+    }
+
+    fn random(&self) -> Option<usize> {
+        if self.tiles.is_empty() {
+            return None;
         }
+        let index = rand::thread_rng().gen_range(0..self.tiles.len());
+        Some(self.tiles[index])
     }
 }
 
-pub struct MeshFactors {
-    pub to_x_coord_factor: f32,
-    pub to_y_coord_factor: f32,
-    pub shorten_width: f32,
-    pub shorten_height: f32,
+/// Creates a quad covering the tile at `pos`, textured with `sprite`
+/// and drawn in `layer`. The position is in tiles and may be fractional,
+/// the quad is in world units with the tile spanning from `pos[1] - 1`
+/// to `pos[1]` vertically.
+pub fn tile_quad(pos: [f32; 2], sprite: Sprite, layer: Layer) -> Quad {
+    // Tiles are slightly shortened to keep the grid lines visible.
+    let shorten_by = 0.05;
+    let (tex_top_left, tex_bottom_right) = sprite.tex_coords();
+
+    Quad {
+        top_left: [pos[0] + shorten_by, pos[1] - shorten_by, layer.depth()],
+        bottom_right: [pos[0] + 1.0 - shorten_by, pos[1] - 1.0 + shorten_by],
+        tex_top_left,
+        tex_bottom_right,
+        palette: sprite.palette_color() as u32,
+        ..Default::default()
+    }
 }
 
 pub trait ElementMesh {
-    fn generate_mesh(&self, offsets: MeshOffsets) -> Vec<Quad>;
+    fn generate_mesh(&self) -> Vec<Quad>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(occupied: &[bool]) -> Vec<Tile> {
+        occupied
+            .iter()
+            .enumerate()
+            .map(|(index, &is_occupied)| Tile {
+                is_occupied,
+                pos: (index as u32, 1).into(),
+            })
+            .collect()
+    }
+
+    // Checks that the set and its index agree, returning the free tiles.
+    fn free(free_tiles: &FreeTiles) -> Vec<usize> {
+        for (position, &tile) in free_tiles.tiles.iter().enumerate() {
+            assert_eq!(free_tiles.indices[tile], Some(position));
+        }
+        let mut free: Vec<usize> = (0..free_tiles.indices.len())
+            .filter(|&tile| free_tiles.indices[tile].is_some())
+            .collect();
+        assert_eq!(free.len(), free_tiles.tiles.len());
+        free.sort_unstable();
+        free
+    }
+
+    #[test]
+    fn insert_then_remove() {
+        let mut free_tiles = FreeTiles::new(&tiles(&[true; 4]));
+        assert_eq!(free(&free_tiles), []);
+        free_tiles.insert(2);
+        free_tiles.insert(2);
+        assert_eq!(free(&free_tiles), [2]);
+        free_tiles.remove(2);
+        assert_eq!(free(&free_tiles), []);
+        free_tiles.remove(2);
+        assert_eq!(free(&free_tiles), []);
+    }
+
+    #[test]
+    fn remove_last_tile() {
+        let mut free_tiles = FreeTiles::new(&tiles(&[false; 4]));
+        free_tiles.remove(3);
+        assert_eq!(free(&free_tiles), [0, 1, 2]);
+    }
+
+    #[test]
+    fn remove_middle_tile() {
+        let mut free_tiles = FreeTiles::new(&tiles(&[false; 5]));
+        free_tiles.remove(1);
+        assert_eq!(free(&free_tiles), [0, 2, 3, 4]);
+        // The tile moved into the gap can still be removed.
+        free_tiles.remove(4);
+        assert_eq!(free(&free_tiles), [0, 2, 3]);
+        free_tiles.insert(1);
+        assert_eq!(free(&free_tiles), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn random_picks_free_tiles() {
+        let mut free_tiles =
+            FreeTiles::new(&tiles(&[true, false, true, false]));
+        for _ in 0..32 {
+            let tile = free_tiles.random().unwrap();
+            assert!(tile == 1 || tile == 3);
+        }
+        free_tiles.remove(1);
+        free_tiles.remove(3);
+        assert_eq!(free_tiles.random(), None);
+    }
+
+    #[test]
+    fn random_on_empty_set() {
+        let free_tiles = FreeTiles::new(&[]);
+        assert_eq!(free_tiles.random(), None);
+    }
+
+    fn fill(board: &mut Board, size: u32) {
+        for y in 1..=size {
+            for x in 0..size {
+                board.update_tiles_data(TileUpdateData {
+                    occupy: (x, y).into(),
+                    unoccupy: None,
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn random_free_tile_is_the_last_one() {
+        let mut board = Board::new(3);
+        fill(&mut board, 3);
+        board.update_tiles_data(TileUpdateData {
+            occupy: (0, 1).into(),
+            unoccupy: Some((2, 3).into()),
+        });
+        assert_eq!(board.random_free_tile(), Ok((2, 3).into()));
+    }
+
+    #[test]
+    fn filled_board() {
+        let mut board = Board::new(3);
+        fill(&mut board, 3);
+        assert_eq!(board.random_free_tile(), Err(BoardFilled));
+    }
 }
@@ -0,0 +1,317 @@
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    game::{GameOver, Outcome},
+    graphics::{Graphics, Renderable},
+    input::{Action, InputManager},
+    scores::{format_time, HighScores},
+    settings::{ColorMode, Rules, Settings, Walls},
+    shaders::ChangedShaders,
+    text::TextRenderer,
+    theme::Theme,
+    ui::{Ui, UiState},
+};
+
+#[derive(Debug, Clone, Copy)]
+enum Screen {
+    Main,
+    Options,
+    Accessibility,
+    Effects,
+    Rules,
+    Controls,
+    HighScores,
+    GameOver(GameOver),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuAction {
+    NewGame,
+    Resume,
+    Quit,
+}
+
+pub struct Menu {
+    screen: Screen,
+    ui_state: UiState,
+    text: TextRenderer,
+    // Key binding slot waiting for a key press on the controls screen.
+    capturing: Option<(Action, usize)>,
+}
+
+impl Menu {
+    pub fn new(gfx: &Graphics, theme: &Theme) -> Self {
+        Self {
+            screen: Screen::Main,
+            ui_state: UiState::default(),
+            text: TextRenderer::new(gfx, theme),
+            capturing: None,
+        }
+    }
+
+    pub fn set_theme(&self, gfx: &Graphics, theme: &Theme) {
+        self.text.set_theme(gfx, theme);
+    }
+
+    pub fn reload_shaders(&mut self, gfx: &Graphics, changed: &ChangedShaders) {
+        self.text.reload_shaders(gfx, changed);
+    }
+
+    /// Opens the main menu.
+    pub fn open(&mut self) {
+        self.switch_to(Screen::Main);
+    }
+
+    /// Opens the game over screen with the result of the game.
+    pub fn show_game_over(&mut self, game_over: GameOver) {
+        self.switch_to(Screen::GameOver(game_over));
+    }
+
+    /// Lays out the current screen. `can_resume` tells if there is
+    /// a paused game to return to. Without `input` the screen is only
+    /// laid out, nothing can be changed.
+    pub fn update(
+        &mut self,
+        gfx: &Graphics,
+        input: Option<&InputManager>,
+        settings: &mut Settings,
+        high_scores: &HighScores,
+        themes: &[Theme],
+        can_resume: bool,
+    ) -> Option<MenuAction> {
+        let key = input.and_then(InputManager::get_pressed_key);
+        let escape = key == Some(VirtualKeyCode::Escape);
+        let mut action = None;
+
+        if let Some((binding, slot)) = self.capturing {
+            match key {
+                Some(VirtualKeyCode::Escape) => self.capturing = None,
+                Some(key) => {
+                    settings.key_bindings.keys_mut(binding)[slot] = key;
+                    self.capturing = None;
+                }
+                None => (),
+            }
+            self.text.queue(
+                &format!("PRESS A KEY FOR {}", binding.name()),
+                [10.0, 10.0],
+                2.0,
+            );
+            self.text.prepare(gfx);
+            return None;
+        }
+
+        let mut ui = Ui::begin(gfx, &mut self.text, &mut self.ui_state, input);
+        let mut next_screen = None;
+        match self.screen {
+            Screen::Main => {
+                ui.title("SNAKE");
+                if can_resume && ui.button("RESUME") {
+                    action = Some(MenuAction::Resume);
+                }
+                if ui.button("NEW GAME") {
+                    action = Some(MenuAction::NewGame);
+                }
+                if ui.button("OPTIONS") {
+                    next_screen = Some(Screen::Options);
+                }
+                if ui.button("RULES") {
+                    next_screen = Some(Screen::Rules);
+                }
+                if ui.button("CONTROLS") {
+                    next_screen = Some(Screen::Controls);
+                }
+                if ui.button("HIGH SCORES") {
+                    next_screen = Some(Screen::HighScores);
+                }
+                if ui.button("QUIT") {
+                    action = Some(MenuAction::Quit);
+                }
+                if escape {
+                    action = Some(if can_resume {
+                        MenuAction::Resume
+                    } else {
+                        MenuAction::Quit
+                    });
+                }
+            }
+            Screen::Options => {
+                ui.title("OPTIONS");
+                ui.slider("SPEED", &mut settings.ticks_per_second, 2..=20);
+                ui.slider("VIEW SIZE", &mut settings.view_size, 10..=50);
+                let names: Vec<&str> =
+                    themes.iter().map(|theme| theme.name.as_str()).collect();
+                ui.list("THEME", &mut settings.theme, &names);
+                ui.toggle("SHOW HUD", &mut settings.show_hud);
+                ui.toggle("SHOW MINIMAP", &mut settings.show_minimap);
+                let grid = &mut settings.grid;
+                ui.slider("GRID WIDTH", &mut grid.thickness, 1..=6);
+                let mut opacity = (grid.opacity * 100.0).round() as u32;
+                if ui.slider("GRID OPACITY", &mut opacity, 0..=100) {
+                    grid.opacity = opacity as f32 / 100.0;
+                }
+                ui.toggle("CHECKERBOARD", &mut grid.checkerboard);
+                if ui.button("EFFECTS") {
+                    next_screen = Some(Screen::Effects);
+                }
+                if ui.button("ACCESSIBILITY") {
+                    next_screen = Some(Screen::Accessibility);
+                }
+                ui.space();
+                if ui.button("BACK") || escape {
+                    next_screen = Some(Screen::Main);
+                }
+            }
+            Screen::Effects => {
+                let effects = &mut settings.effects;
+                ui.title("EFFECTS");
+                ui.toggle("BLOOM", &mut effects.bloom);
+                ui.toggle("COLOR GRADING", &mut effects.color_grading);
+                ui.toggle("VIGNETTE", &mut effects.vignette);
+                ui.toggle("CRT", &mut effects.crt);
+                ui.toggle("RETRO PIXELS", &mut effects.retro);
+                ui.toggle("SCREEN SHAKE", &mut settings.screen_shake);
+                ui.space();
+                if ui.button("BACK") || escape {
+                    next_screen = Some(Screen::Options);
+                }
+            }
+            Screen::Accessibility => {
+                let accessibility = &mut settings.accessibility;
+                ui.title("ACCESSIBILITY");
+                let names = ColorMode::ALL.map(|mode| mode.name());
+                let mut color_mode = ColorMode::ALL
+                    .iter()
+                    .position(|mode| *mode == accessibility.color_mode)
+                    .unwrap();
+                if ui.list("COLORS", &mut color_mode, &names) {
+                    accessibility.color_mode = ColorMode::ALL[color_mode];
+                }
+                ui.toggle("SHAPE CUES", &mut accessibility.shape_cues);
+                ui.toggle("REDUCED MOTION", &mut accessibility.reduced_motion);
+                ui.space();
+                if ui.button("BACK") || escape {
+                    next_screen = Some(Screen::Options);
+                }
+            }
+            Screen::Rules => {
+                let rules = &mut settings.rules;
+                ui.title("RULES");
+                let sizes = Rules::MAP_SIZES.map(|size| size.to_string());
+                let sizes: Vec<&str> =
+                    sizes.iter().map(|size| size.as_str()).collect();
+                let mut map_size = Rules::MAP_SIZES
+                    .iter()
+                    .position(|size| *size == rules.map_size)
+                    .unwrap_or(0);
+                if ui.list("MAP SIZE", &mut map_size, &sizes) {
+                    rules.map_size = Rules::MAP_SIZES[map_size];
+                }
+                let names = Walls::ALL.map(|walls| walls.name());
+                let mut walls = Walls::ALL
+                    .iter()
+                    .position(|walls| *walls == rules.walls)
+                    .unwrap();
+                if ui.list("WALLS", &mut walls, &names) {
+                    rules.walls = Walls::ALL[walls];
+                }
+                let mut apple_worth = rules.apple_worth as u32;
+                if ui.slider("APPLE WORTH", &mut apple_worth, 1..=10) {
+                    rules.apple_worth = apple_worth as usize;
+                }
+                let mut starting_length = rules.starting_length as u32;
+                if ui.slider("START LENGTH", &mut starting_length, 1..=10) {
+                    rules.starting_length = starting_length as usize;
+                }
+                let names = Rules::TARGET_LENGTHS.map(Rules::target_name);
+                let names: Vec<&str> =
+                    names.iter().map(|name| name.as_str()).collect();
+                let mut target = Rules::TARGET_LENGTHS
+                    .iter()
+                    .position(|target| *target == rules.target_length)
+                    .unwrap_or(0);
+                if ui.list("TARGET", &mut target, &names) {
+                    rules.target_length = Rules::TARGET_LENGTHS[target];
+                }
+                ui.label("APPLIED TO NEW GAMES");
+                ui.space();
+                if ui.button("BACK") || escape {
+                    next_screen = Some(Screen::Main);
+                }
+            }
+            Screen::Controls => {
+                ui.title("CONTROLS");
+                for binding in Action::ALL {
+                    let keys = settings.key_bindings.keys(binding);
+                    for (slot, key) in keys.iter().enumerate() {
+                        let name = if slot == 0 {
+                            binding.name().to_string()
+                        } else {
+                            format!("{} ALT", binding.name())
+                        };
+                        if ui.button(&format!("{}: {:?}", name, key)) {
+                            self.capturing = Some((binding, slot));
+                        }
+                    }
+                }
+                ui.space();
+                if ui.button("BACK") || escape {
+                    next_screen = Some(Screen::Main);
+                }
+            }
+            Screen::HighScores => {
+                ui.title("HIGH SCORES");
+                if high_scores.scores().is_empty() {
+                    ui.label("NO SCORES YET");
+                }
+                for (i, entry) in high_scores.scores().iter().enumerate() {
+                    ui.label(&format!(
+                        "{:>2}. {:>5} {:>6}{}",
+                        i + 1,
+                        entry.score,
+                        format_time(entry.time),
+                        if entry.won { " WON" } else { "    " }
+                    ));
+                }
+                ui.space();
+                if ui.button("BACK") || escape {
+                    next_screen = Some(Screen::Main);
+                }
+            }
+            Screen::GameOver(game_over) => {
+                ui.title(match game_over.outcome {
+                    Outcome::Died => "GAME OVER",
+                    Outcome::ReachedTarget => "YOU WIN",
+                    Outcome::FilledBoard => "PERFECT GAME",
+                });
+                ui.label(&format!("SCORE {}", game_over.score));
+                ui.label(&format!("TIME {}", format_time(game_over.time)));
+                ui.space();
+                if ui.button("NEW GAME") {
+                    action = Some(MenuAction::NewGame);
+                }
+                if ui.button("MAIN MENU") || escape {
+                    next_screen = Some(Screen::Main);
+                }
+            }
+        }
+        ui.finish();
+        self.text.prepare(gfx);
+
+        if let Some(screen) = next_screen {
+            self.switch_to(screen);
+        }
+        action
+    }
+
+    fn switch_to(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.ui_state.reset();
+    }
+}
+
+impl Renderable for Menu {
+    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        self.text.render(rpass);
+    }
+}
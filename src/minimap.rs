@@ -0,0 +1,255 @@
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    batch::{BatchSlot, QuadBatch},
+    camera::Camera,
+    game_elements::Position,
+    graphics::{Graphics, Layer, Quad, QuadShape, Renderable},
+    map::Map,
+    shaders::ChangedShaders,
+    texture::Texture,
+    theme::{Palette, PaletteColor, Theme},
+};
+
+// Fraction of the shorter window side taken up by the minimap.
+const MINIMAP_SCALE: f32 = 0.25;
+const MINIMAP_MARGIN: f32 = 10.0;
+// Width of the viewport outline in pixels.
+const OUTLINE_WIDTH: f32 = 2.0;
+// Padding and corner radius of the panel behind the map, in pixels.
+const FRAME_PADDING: f32 = 4.0;
+const FRAME_RADIUS: f32 = 6.0;
+const FRAME_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const OUTLINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+const FREE_COLOR: Rgba<u8> = Rgba([20, 20, 20, 170]);
+const OCCUPIED_COLOR: Rgba<u8> = Rgba([70, 160, 60, 255]);
+const HEAD_COLOR: Rgba<u8> = Rgba([255, 230, 60, 255]);
+const APPLE_COLOR: Rgba<u8> = Rgba([220, 40, 40, 255]);
+
+/// Scaled down overview of the whole map in the top right corner
+/// of the window, with the part of the map in view outlined.
+///
+/// Every tile is a texel of a texture redrawn from the map tiles.
+pub struct Minimap {
+    map_size: u32,
+    image: RgbaImage,
+    texture: Texture,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    // Maps pixel positions to clip space.
+    camera: Camera,
+    palette: Palette,
+    batch: QuadBatch,
+    slot: BatchSlot,
+    // Set when the whole map fits into the view.
+    hidden: bool,
+    colors: TileColors,
+}
+
+/// Colors of the minimap texels.
+struct TileColors {
+    occupied: Rgba<u8>,
+    head: Rgba<u8>,
+    apple: Rgba<u8>,
+}
+
+impl TileColors {
+    /// Themes which recolor the sprites are used for the minimap as well,
+    /// other themes keep the default colors.
+    fn new(theme: &Theme) -> Self {
+        if !theme.recolor {
+            return Self {
+                occupied: OCCUPIED_COLOR,
+                head: HEAD_COLOR,
+                apple: APPLE_COLOR,
+            };
+        }
+        let to_rgba = |color: [f32; 4]| {
+            Rgba(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+        };
+        Self {
+            occupied: to_rgba(theme.body),
+            head: to_rgba(theme.head),
+            apple: to_rgba(theme.apple),
+        }
+    }
+}
+
+impl Minimap {
+    pub fn new(
+        gfx: &Graphics,
+        camera_layout: &wgpu::BindGroupLayout,
+        texture_layout: &wgpu::BindGroupLayout,
+        palette_layout: &wgpu::BindGroupLayout,
+        map_size: usize,
+        theme: &Theme,
+    ) -> Self {
+        let map_size = map_size as u32;
+        let image = RgbaImage::from_pixel(map_size, map_size, FREE_COLOR);
+        let texture = Texture::from_image(
+            gfx,
+            &image,
+            "Minimap Texture",
+            wgpu::FilterMode::Nearest,
+        );
+        let bind_group = texture.bind_group(gfx, texture_layout);
+        let pipeline = Quad::create_pipeline(
+            gfx,
+            camera_layout,
+            texture_layout,
+            palette_layout,
+        );
+
+        let mut batch = QuadBatch::new(gfx, "Minimap Quad Buffer");
+        // The frame, the map and the four sides of the outline.
+        let slot = batch.allocate(6);
+
+        Self {
+            map_size,
+            image,
+            texture,
+            bind_group,
+            pipeline,
+            camera: Camera::new(gfx, camera_layout),
+            palette: Palette::new(gfx, palette_layout, theme),
+            batch,
+            slot,
+            hidden: true,
+            colors: TileColors::new(theme),
+        }
+    }
+
+    /// Redraws the texture from the occupied tiles of the map.
+    pub fn update_tiles(
+        &mut self,
+        gfx: &Graphics,
+        map: &Map,
+        head: Position,
+        apple: Position,
+    ) {
+        for tile in &map.board.tiles {
+            let color = if tile.is_occupied {
+                self.colors.occupied
+            } else {
+                FREE_COLOR
+            };
+            self.put_tile(tile.pos, color);
+        }
+        self.put_tile(head, self.colors.head);
+        self.put_tile(apple, self.colors.apple);
+        self.texture.write(gfx, &self.image);
+    }
+
+    /// Lays out the minimap and outlines the visible world rectangle
+    /// from `view_min` to `view_max`. The minimap is hidden while
+    /// the whole map is in view.
+    pub fn update_view(
+        &mut self,
+        gfx: &Graphics,
+        view_min: [f32; 2],
+        view_max: [f32; 2],
+    ) {
+        let size = self.map_size as f32;
+        self.hidden = view_min[0] <= 0.0
+            && view_min[1] <= 0.0
+            && view_max[0] >= size
+            && view_max[1] >= size;
+        if self.hidden {
+            return;
+        }
+
+        let win_width = gfx.config.width as f32;
+        let side = win_width.min(gfx.config.height as f32) * MINIMAP_SCALE;
+        let left = win_width - MINIMAP_MARGIN - side;
+        let top = MINIMAP_MARGIN;
+        let to_pixels = |pos: [f32; 2]| {
+            [
+                left + pos[0].clamp(0.0, size) / size * side,
+                top + (size - pos[1].clamp(0.0, size)) / size * side,
+            ]
+        };
+
+        let depth = Layer::Ui.depth();
+        let pad = FRAME_PADDING;
+        let frame_quad = Quad {
+            top_left: [left - pad, top - pad, depth],
+            bottom_right: [left + side + pad, top + side + pad],
+            color: FRAME_COLOR,
+            corner_radius: FRAME_RADIUS,
+            shape: QuadShape::RoundedRect as u32,
+            ..Default::default()
+        };
+        let map_quad = Quad {
+            top_left: [left, top, depth],
+            bottom_right: [left + side, top + side],
+            tex_top_left: [0.0, 0.0],
+            tex_bottom_right: [1.0, 1.0],
+            palette: PaletteColor::Neutral as u32,
+            ..Default::default()
+        };
+
+        let [view_left, view_top] = to_pixels([view_min[0], view_max[1]]);
+        let [view_right, view_bottom] = to_pixels([view_max[0], view_min[1]]);
+        let outline = |[x0, y0]: [f32; 2], [x1, y1]: [f32; 2]| Quad {
+            top_left: [x0, y0, depth],
+            bottom_right: [x1, y1],
+            color: OUTLINE_COLOR,
+            shape: QuadShape::Rect as u32,
+            ..Default::default()
+        };
+        let w = OUTLINE_WIDTH;
+
+        self.batch.write(
+            self.slot,
+            &[
+                frame_quad,
+                map_quad,
+                outline([view_left, view_top], [view_right, view_top + w]),
+                outline(
+                    [view_left, view_bottom - w],
+                    [view_right, view_bottom],
+                ),
+                outline([view_left, view_top], [view_left + w, view_bottom]),
+                outline([view_right - w, view_top], [view_right, view_bottom]),
+            ],
+        );
+        self.batch.upload(gfx);
+        self.camera.screen(gfx);
+    }
+
+    /// Switches the colors, the tiles have to be redrawn afterwards.
+    pub fn set_theme(&mut self, gfx: &Graphics, theme: &Theme) {
+        self.palette.set_theme(gfx, theme);
+        self.colors = TileColors::new(theme);
+    }
+
+    pub fn reload_shaders(&mut self, gfx: &Graphics, changed: &ChangedShaders) {
+        Quad::reload_pipeline(gfx, &mut self.pipeline, changed);
+    }
+
+    /// Colors the pixel of a tile, ignoring positions off the board.
+    /// Columns count from 0 and rows from 1, like the tiles of the map.
+    fn put_tile(&mut self, pos: Position, color: Rgba<u8>) {
+        if pos.x_tile >= self.map_size
+            || !(1..=self.map_size).contains(&pos.y_tile)
+        {
+            return;
+        }
+        self.image
+            .put_pixel(pos.x_tile, self.map_size - pos.y_tile, color);
+    }
+}
+
+impl Renderable for Minimap {
+    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        if self.hidden {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.camera.bind_group, &[]);
+        rpass.set_bind_group(1, &self.bind_group, &[]);
+        rpass.set_bind_group(2, &self.palette.bind_group, &[]);
+        self.batch.render(rpass);
+    }
+}
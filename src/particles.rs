@@ -0,0 +1,242 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use rand::Rng;
+
+use crate::{
+    batch::{BatchSlot, QuadBatch},
+    graphics::{Layer, Quad, QuadShape},
+    texture::Sprite,
+    theme::PaletteColor,
+};
+
+// Upper bound of live particles, new ones are dropped past it.
+const MAX_PARTICLES: usize = 2048;
+
+/// Burst of particles thrown from the apple when it's eaten.
+pub const EAT_BURST: Burst = Burst {
+    count: 24,
+    speed: [2.0, 6.0],
+    lifetime: [0.3, 0.6],
+    gravity: -6.0,
+    drag: 3.0,
+    spin: 0.0,
+    look: Look::Shape(QuadShape::Circle),
+    palette: PaletteColor::Apple,
+    size: Curve(&[(0.0, 0.3), (1.0, 0.05)]),
+    color: Curve(&[
+        (0.0, [1.0, 0.9, 0.6, 1.0]),
+        (0.3, [1.0, 0.35, 0.25, 1.0]),
+        (1.0, [0.8, 0.1, 0.1, 0.0]),
+    ]),
+};
+
+/// Piece of a dead snake thrown out from every segment as it dissolves.
+pub const DEATH_SCATTER: Burst = Burst {
+    count: 1,
+    speed: [1.0, 4.0],
+    lifetime: [0.6, 1.0],
+    gravity: -10.0,
+    drag: 1.0,
+    spin: 8.0,
+    look: Look::Sprite(Sprite::Vertical),
+    palette: PaletteColor::Body,
+    size: Curve(&[(0.0, 0.9), (1.0, 0.4)]),
+    color: Curve(&[
+        (0.0, [1.0; 4]),
+        (0.6, [1.0; 4]),
+        (1.0, [1.0, 1.0, 1.0, 0.0]),
+    ]),
+};
+
+/// Sparkle around a newly spawned apple.
+pub const SPAWN_SPARKLE: Burst = Burst {
+    count: 10,
+    speed: [0.5, 1.5],
+    lifetime: [0.3, 0.5],
+    gravity: 0.0,
+    drag: 2.0,
+    spin: 4.0,
+    look: Look::Shape(QuadShape::Rect),
+    palette: PaletteColor::Neutral,
+    size: Curve(&[(0.0, 0.05), (0.3, 0.15), (1.0, 0.0)]),
+    color: Curve(&[(0.0, [1.0, 1.0, 1.0, 1.0]), (1.0, [1.0, 0.9, 0.4, 0.0])]),
+};
+
+/// What the particles of a burst look like.
+#[derive(Debug, Clone, Copy)]
+pub enum Look {
+    Shape(QuadShape),
+    Sprite(Sprite),
+}
+
+/// Value changing over the lifetime of a particle, linearly interpolated
+/// between `(time, value)` keys sorted by time from 0 to 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Curve<T: 'static>(pub &'static [(f32, T)]);
+
+impl<T: Lerp> Curve<T> {
+    fn sample(&self, t: f32) -> T {
+        let keys = self.0;
+        let next = keys.iter().position(|(time, _)| *time > t);
+        match next {
+            Some(0) => keys[0].1,
+            Some(i) => {
+                let (from_time, from) = keys[i - 1];
+                let (to_time, to) = keys[i];
+                from.lerp(to, (t - from_time) / (to_time - from_time))
+            }
+            None => keys[keys.len() - 1].1,
+        }
+    }
+}
+
+pub trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for [f32; 4] {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        [0, 1, 2, 3].map(|i| self[i].lerp(other[i], t))
+    }
+}
+
+/// Emitter settings of a group of particles thrown out at once.
+#[derive(Debug, Clone, Copy)]
+pub struct Burst {
+    // Particles per emitted position.
+    pub count: usize,
+    // Range of the starting speed in tiles per second, in a random
+    // direction.
+    pub speed: [f32; 2],
+    // Range of the lifetime in seconds.
+    pub lifetime: [f32; 2],
+    // Vertical acceleration in tiles per second squared.
+    pub gravity: f32,
+    // Fraction of the velocity lost per second.
+    pub drag: f32,
+    // Highest rotation speed in radians per second.
+    pub spin: f32,
+    pub look: Look,
+    pub palette: PaletteColor,
+    // Size in tiles over the lifetime.
+    pub size: Curve<f32>,
+    // Color over the lifetime, fading out with its alpha.
+    pub color: Curve<[f32; 4]>,
+}
+
+#[derive(Debug)]
+struct Particle {
+    pos: [f32; 2],
+    velocity: [f32; 2],
+    rotation: f32,
+    angular_velocity: f32,
+    age: f32,
+    lifetime: f32,
+    burst: &'static Burst,
+}
+
+/// Short lived quads thrown out by game events, drawn over the game from
+/// a slot of the game batch.
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    slot: BatchSlot,
+    // Set while the slot holds quads of live particles.
+    drawn: bool,
+}
+
+impl ParticleSystem {
+    pub fn new(batch: &mut QuadBatch) -> Self {
+        Self {
+            particles: Vec::new(),
+            slot: batch.allocate(64),
+            drawn: false,
+        }
+    }
+
+    /// Throws out the particles of `burst` from the world position `pos`.
+    pub fn emit(&mut self, burst: &'static Burst, pos: [f32; 2]) {
+        let mut rng = rand::thread_rng();
+        let room = MAX_PARTICLES.saturating_sub(self.particles.len());
+        for _ in 0..burst.count.min(room) {
+            let angle = rng.gen_range(0.0..TAU);
+            let speed = rng.gen_range(burst.speed[0]..=burst.speed[1]);
+            self.particles.push(Particle {
+                pos,
+                velocity: [angle.cos() * speed, angle.sin() * speed],
+                rotation: if burst.spin > 0.0 {
+                    rng.gen_range(0.0..TAU)
+                } else {
+                    0.0
+                },
+                angular_velocity: rng.gen_range(-burst.spin..=burst.spin),
+                age: 0.0,
+                lifetime: rng.gen_range(burst.lifetime[0]..=burst.lifetime[1]),
+                burst,
+            });
+        }
+    }
+
+    /// Moves the particles by `frame_time`, removes the expired ones and
+    /// writes the rest into the batch.
+    pub fn update(&mut self, batch: &mut QuadBatch, frame_time: Duration) {
+        if self.particles.is_empty() && !self.drawn {
+            return;
+        }
+
+        let dt = frame_time.as_secs_f32();
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            let burst = particle.burst;
+            let damping = (-burst.drag * dt).exp();
+            particle.velocity[0] *= damping;
+            particle.velocity[1] =
+                particle.velocity[1] * damping + burst.gravity * dt;
+            particle.pos[0] += particle.velocity[0] * dt;
+            particle.pos[1] += particle.velocity[1] * dt;
+            particle.rotation += particle.angular_velocity * dt;
+            particle.age < particle.lifetime
+        });
+
+        let quads: Vec<Quad> =
+            self.particles.iter().map(Particle::quad).collect();
+        batch.write(self.slot, &quads);
+        self.drawn = !quads.is_empty();
+    }
+
+    /// Removes all particles.
+    pub fn clear(&mut self, batch: &mut QuadBatch) {
+        self.particles.clear();
+        batch.write(self.slot, &[]);
+        self.drawn = false;
+    }
+}
+
+impl Particle {
+    fn quad(&self) -> Quad {
+        let t = self.age / self.lifetime;
+        let half = self.burst.size.sample(t) / 2.0;
+        let [x, y] = self.pos;
+        let mut quad = Quad {
+            top_left: [x - half, y + half, Layer::Effects.depth()],
+            bottom_right: [x + half, y - half],
+            palette: self.burst.palette as u32,
+            color: self.burst.color.sample(t),
+            rotation: self.rotation,
+            ..Default::default()
+        };
+        match self.burst.look {
+            Look::Shape(shape) => quad.shape = shape as u32,
+            Look::Sprite(sprite) => {
+                (quad.tex_top_left, quad.tex_bottom_right) =
+                    sprite.tex_coords();
+            }
+        }
+        quad
+    }
+}
@@ -0,0 +1,483 @@
+use std::{borrow::Cow, time::Instant};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::Viewport,
+    graphics::{self, Graphics, Layer},
+    settings::PostEffects,
+    shaders::{self, ChangedShaders},
+};
+
+const COMMON_SHADER: &str = "post/common.wgsl";
+const UPSCALE_SHADER: &str = "post/upscale.wgsl";
+
+/// Fullscreen effect applied to the drawn scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Bloom,
+    ColorGrading,
+    Vignette,
+    Crt,
+}
+
+impl Effect {
+    /// All effects in the order they are applied in.
+    pub const ALL: [Effect; 4] = [
+        Effect::Bloom,
+        Effect::ColorGrading,
+        Effect::Vignette,
+        Effect::Crt,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Effect::Bloom => "Bloom",
+            Effect::ColorGrading => "Color Grading",
+            Effect::Vignette => "Vignette",
+            Effect::Crt => "CRT",
+        }
+    }
+
+    /// Fragment shader of the effect, appended to the common shader.
+    fn shader(&self) -> &'static str {
+        match self {
+            Effect::Bloom => "post/bloom.wgsl",
+            Effect::ColorGrading => "post/grading.wgsl",
+            Effect::Vignette => "post/vignette.wgsl",
+            Effect::Crt => "post/crt.wgsl",
+        }
+    }
+
+    fn enabled(&self, effects: &PostEffects) -> bool {
+        match self {
+            Effect::Bloom => effects.bloom,
+            Effect::ColorGrading => effects.color_grading,
+            Effect::Vignette => effects.vignette,
+            Effect::Crt => effects.crt,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParamsUniform {
+    resolution: [f32; 2],
+    time: f32,
+    scale: f32,
+    glow_depth: [f32; 2],
+    offset: [f32; 2],
+}
+
+/// Color texture the scene or an effect is drawn into, bound as the source
+/// of the next effect.
+struct Target {
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Chain of fullscreen effects between the scene and the window.
+///
+/// The scene is drawn into an offscreen target, then every enabled effect
+/// draws the output of the previous one into the next target, the last
+/// one into the window. Targets are ping-ponged, so the chain only needs
+/// two besides the scene. All targets have the size of the viewport, which
+/// is scaled up to the window by a last pass in retro mode.
+pub struct PostProcess {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: wgpu::Buffer,
+    pipeline_layout: wgpu::PipelineLayout,
+    // Indexed like `Effect::ALL`.
+    pipelines: Vec<wgpu::RenderPipeline>,
+    upscale: wgpu::RenderPipeline,
+    viewport: Viewport,
+    // Depth buffer of the scene, read by the effects.
+    depth_view: wgpu::TextureView,
+    scene: Target,
+    ping_pong: [Target; 2],
+    start: Instant,
+}
+
+impl PostProcess {
+    pub fn new(gfx: &Graphics, viewport: Viewport) -> Self {
+        let layout = gfx.device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Process Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            },
+        );
+        let sampler = gfx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Process Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let params =
+            gfx.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Post Process Params Buffer"),
+                    contents: bytemuck::cast_slice(&[Self::params_uniform(
+                        viewport, 0.0,
+                    )]),
+                    usage: wgpu::BufferUsages::UNIFORM
+                        | wgpu::BufferUsages::COPY_DST,
+                });
+
+        let pipeline_layout = gfx.device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Process Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            },
+        );
+        let pipelines = Effect::ALL
+            .iter()
+            .map(|effect| {
+                Self::create_pipeline(
+                    gfx,
+                    &pipeline_layout,
+                    effect.label(),
+                    effect.shader(),
+                )
+            })
+            .collect();
+        let upscale = Self::create_pipeline(
+            gfx,
+            &pipeline_layout,
+            "Upscale",
+            UPSCALE_SHADER,
+        );
+
+        let depth_view =
+            Graphics::create_depth_view(&gfx.device, viewport.size);
+        let create = || {
+            Self::create_target(
+                gfx,
+                viewport,
+                &layout,
+                &sampler,
+                &params,
+                &depth_view,
+            )
+        };
+        let scene = create();
+        let ping_pong = [create(), create()];
+
+        Self {
+            layout,
+            sampler,
+            params,
+            pipeline_layout,
+            pipelines,
+            upscale,
+            viewport,
+            depth_view,
+            scene,
+            ping_pong,
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the enabled effects in the order they are applied in.
+    pub fn chain(effects: &PostEffects) -> Vec<Effect> {
+        Effect::ALL
+            .into_iter()
+            .filter(|effect| effect.enabled(effects))
+            .collect()
+    }
+
+    /// Whether the scene has to be drawn into the scene target instead of
+    /// straight into the window.
+    pub fn is_offscreen(&self, chain: &[Effect]) -> bool {
+        !chain.is_empty() || self.viewport.is_scaled()
+    }
+
+    /// Target the scene is drawn into when it's drawn offscreen.
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.scene.view
+    }
+
+    /// Depth attachment the scene is drawn with, sized to the viewport.
+    pub fn depth_attachment(
+        &self,
+    ) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        graphics::depth_attachment(&self.depth_view)
+    }
+
+    /// Applies the chain to the drawn scene and scales it up if needed,
+    /// the last pass drawing into `output`. Effects changing over time
+    /// are frozen if `animate` isn't set.
+    pub fn apply(
+        &self,
+        gfx: &Graphics,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        chain: &[Effect],
+        animate: bool,
+    ) {
+        let time = if animate {
+            self.start.elapsed().as_secs_f32()
+        } else {
+            0.0
+        };
+        gfx.queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::cast_slice(&[Self::params_uniform(self.viewport, time)]),
+        );
+
+        let mut passes: Vec<_> = chain
+            .iter()
+            .map(|effect| {
+                let index =
+                    Effect::ALL.iter().position(|e| e == effect).unwrap();
+                (effect.label(), &self.pipelines[index])
+            })
+            .collect();
+        if self.viewport.is_scaled() {
+            passes.push(("Upscale", &self.upscale));
+        }
+
+        let mut source = &self.scene;
+        for (i, (label, pipeline)) in passes.iter().enumerate() {
+            let target = &self.ping_pong[i % 2];
+            let view = if i + 1 == passes.len() {
+                output
+            } else {
+                &target.view
+            };
+
+            let mut rpass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(label),
+                    color_attachments: &[Some(
+                        wgpu::RenderPassColorAttachment {
+                            view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: true,
+                            },
+                        },
+                    )],
+                    depth_stencil_attachment: None,
+                });
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, &source.bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+
+            source = target;
+        }
+    }
+
+    /// Rebuilds the pipelines whose shaders changed. Pipelines are kept
+    /// if their shaders fail to compile.
+    pub fn reload_shaders(&mut self, gfx: &Graphics, changed: &ChangedShaders) {
+        for (effect, pipeline) in Effect::ALL.iter().zip(&mut self.pipelines) {
+            if changed.any(&[COMMON_SHADER, effect.shader()]) {
+                Self::reload_pipeline(
+                    gfx,
+                    &self.pipeline_layout,
+                    pipeline,
+                    effect.label(),
+                    effect.shader(),
+                );
+            }
+        }
+        if changed.any(&[COMMON_SHADER, UPSCALE_SHADER]) {
+            Self::reload_pipeline(
+                gfx,
+                &self.pipeline_layout,
+                &mut self.upscale,
+                "Upscale",
+                UPSCALE_SHADER,
+            );
+        }
+    }
+
+    /// Recreates the targets at the size of the new viewport.
+    pub fn set_viewport(&mut self, gfx: &Graphics, viewport: Viewport) {
+        self.viewport = viewport;
+        self.depth_view =
+            Graphics::create_depth_view(&gfx.device, viewport.size);
+        let create = || {
+            Self::create_target(
+                gfx,
+                viewport,
+                &self.layout,
+                &self.sampler,
+                &self.params,
+                &self.depth_view,
+            )
+        };
+        self.scene = create();
+        self.ping_pong = [create(), create()];
+    }
+
+    fn params_uniform(viewport: Viewport, time: f32) -> ParamsUniform {
+        ParamsUniform {
+            resolution: viewport.size.map(|pixels| pixels as f32),
+            time,
+            scale: viewport.scale as f32,
+            // Snakes are closer than items, so have a smaller depth.
+            glow_depth: [Layer::Snakes.depth(), Layer::Items.depth()],
+            offset: viewport.offset.map(|pixels| pixels as f32),
+        }
+    }
+
+    fn reload_pipeline(
+        gfx: &Graphics,
+        layout: &wgpu::PipelineLayout,
+        pipeline: &mut wgpu::RenderPipeline,
+        label: &str,
+        fragment_shader: &str,
+    ) {
+        let rebuilt = shaders::try_rebuild(gfx, label, || {
+            Self::create_pipeline(gfx, layout, label, fragment_shader)
+        });
+        if let Some(rebuilt) = rebuilt {
+            *pipeline = rebuilt;
+        }
+    }
+
+    /// Creates the pipeline of a fragment shader appended to the common
+    /// shader.
+    fn create_pipeline(
+        gfx: &Graphics,
+        layout: &wgpu::PipelineLayout,
+        label: &str,
+        fragment_shader: &str,
+    ) -> wgpu::RenderPipeline {
+        let source = format!(
+            "{}\n{}",
+            shaders::source(COMMON_SHADER),
+            shaders::source(fragment_shader)
+        );
+        let shader_module =
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(label),
+                    source: wgpu::ShaderSource::Wgsl(Cow::Owned(source)),
+                });
+
+        gfx.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: gfx.config.format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            })
+    }
+
+    fn create_target(
+        gfx: &Graphics,
+        viewport: Viewport,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        params: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+    ) -> Target {
+        let texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Post Process Target"),
+            size: wgpu::Extent3d {
+                width: viewport.size[0],
+                height: viewport.size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: gfx.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group =
+            gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Process Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(
+                            depth_view,
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: params.as_entire_binding(),
+                    },
+                ],
+            });
+
+        Target { view, bind_group }
+    }
+}
@@ -0,0 +1,180 @@
+use std::{fs, time::Duration};
+
+const HIGH_SCORES_FILE: &str = "highscores.txt";
+const MAX_HIGH_SCORES: usize = 10;
+
+/// Entry of the high score table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighScore {
+    pub score: usize,
+    // Length of the game, the completion time if it was won.
+    pub time: Duration,
+    pub won: bool,
+}
+
+impl HighScore {
+    /// Parses a line in the `score time_ms won` format. Lines with only
+    /// a score, saved by older versions, are accepted as well.
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let score = fields.next()?.parse().ok()?;
+        let time = match fields.next() {
+            Some(millis) => Duration::from_millis(millis.parse().ok()?),
+            None => Duration::ZERO,
+        };
+        let won = fields.next() == Some("won");
+        Some(Self { score, time, won })
+    }
+
+    fn to_line(self) -> String {
+        format!(
+            "{} {} {}\n",
+            self.score,
+            self.time.as_millis(),
+            if self.won { "won" } else { "lost" }
+        )
+    }
+
+    /// Higher scores rank first, equal scores are ranked by the shorter
+    /// completion time.
+    fn ranks_above(&self, other: &Self) -> bool {
+        self.score > other.score
+            || (self.score == other.score
+                && self.won
+                && (!other.won || self.time < other.time))
+    }
+}
+
+/// Best scores, kept in descending order and saved to a file
+/// in the working directory.
+#[derive(Debug)]
+pub struct HighScores {
+    scores: Vec<HighScore>,
+}
+
+impl HighScores {
+    pub fn load() -> Self {
+        let entries: Vec<HighScore> = fs::read_to_string(HIGH_SCORES_FILE)
+            .map(|contents| {
+                contents.lines().filter_map(HighScore::parse).collect()
+            })
+            .unwrap_or_default();
+
+        let mut high_scores = Self { scores: Vec::new() };
+        for entry in entries {
+            high_scores.insert(entry);
+        }
+        high_scores
+    }
+
+    pub fn submit(&mut self, entry: HighScore) {
+        if entry.score == 0 {
+            return;
+        }
+        if self.insert(entry) {
+            self.save();
+        }
+    }
+
+    pub fn best(&self) -> usize {
+        self.scores.first().map(|s| s.score).unwrap_or(0)
+    }
+
+    pub fn scores(&self) -> &[HighScore] {
+        &self.scores
+    }
+
+    /// Returns `true` if the entry made it into the table.
+    fn insert(&mut self, entry: HighScore) -> bool {
+        let index = self
+            .scores
+            .iter()
+            .position(|s| entry.ranks_above(s))
+            .unwrap_or(self.scores.len());
+        if index >= MAX_HIGH_SCORES {
+            return false;
+        }
+        self.scores.insert(index, entry);
+        self.scores.truncate(MAX_HIGH_SCORES);
+        true
+    }
+
+    fn save(&self) {
+        let contents: String =
+            self.scores.iter().map(|s| s.to_line()).collect();
+        if let Err(e) = fs::write(HIGH_SCORES_FILE, contents) {
+            log::error!("Failed to save high scores: {}", e);
+        }
+    }
+}
+
+/// Formats the time as minutes and seconds.
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: usize, secs: u64, won: bool) -> HighScore {
+        HighScore {
+            score,
+            time: Duration::from_secs(secs),
+            won,
+        }
+    }
+
+    #[test]
+    fn line_round_trip() {
+        for entry in [entry(12, 95, true), entry(3, 0, false)] {
+            let line = entry.to_line();
+            assert_eq!(HighScore::parse(line.trim_end()), Some(entry));
+        }
+        // Lines with only a score were saved by older versions.
+        assert_eq!(HighScore::parse("7"), Some(entry(7, 0, false)));
+    }
+
+    #[test]
+    fn malformed_lines() {
+        for line in ["", "   ", "abc", "-3", "1.5", "4 soon won", "4 -10"] {
+            assert_eq!(HighScore::parse(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn ties_ranked_by_time() {
+        let mut high_scores = HighScores { scores: Vec::new() };
+        high_scores.insert(entry(10, 0, false));
+        high_scores.insert(entry(10, 60, true));
+        high_scores.insert(entry(10, 30, true));
+        high_scores.insert(entry(20, 90, false));
+        assert_eq!(
+            high_scores.scores(),
+            [
+                entry(20, 90, false),
+                entry(10, 30, true),
+                entry(10, 60, true),
+                entry(10, 0, false),
+            ]
+        );
+        assert_eq!(high_scores.best(), 20);
+    }
+
+    #[test]
+    fn keeps_top_scores() {
+        let mut high_scores = HighScores { scores: Vec::new() };
+        for score in 1..=MAX_HIGH_SCORES {
+            assert!(high_scores.insert(entry(score, 0, false)));
+        }
+        assert!(!high_scores.insert(entry(1, 0, false)));
+        assert!(high_scores.insert(entry(5, 0, false)));
+        let scores: Vec<usize> =
+            high_scores.scores().iter().map(|s| s.score).collect();
+        assert_eq!(scores.len(), MAX_HIGH_SCORES);
+        assert_eq!(scores[0], MAX_HIGH_SCORES);
+        assert_eq!(*scores.last().unwrap(), 2);
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+}
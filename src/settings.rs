@@ -0,0 +1,181 @@
+use crate::{
+    game::{APPLE_WORTH, MAP_SIZE, STARTING_SNAKE_SIZE},
+    input::KeyBindings,
+};
+
+pub const DEFAULT_TICKS_PER_SECOND: u32 = 6;
+pub const DEFAULT_VIEW_SIZE: u32 = 30;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub ticks_per_second: u32,
+    pub show_hud: bool,
+    pub show_minimap: bool,
+    // Shakes the camera when the snake dies.
+    pub screen_shake: bool,
+    pub grid: GridStyle,
+    // Index into the themes loaded by the engine.
+    pub theme: usize,
+    pub accessibility: Accessibility,
+    pub effects: PostEffects,
+    // Number of tiles visible across the shorter side of the window.
+    pub view_size: u32,
+    pub rules: Rules,
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            ticks_per_second: DEFAULT_TICKS_PER_SECOND,
+            show_hud: true,
+            show_minimap: true,
+            screen_shake: true,
+            grid: GridStyle::default(),
+            theme: 0,
+            accessibility: Accessibility::default(),
+            effects: PostEffects::default(),
+            view_size: DEFAULT_VIEW_SIZE,
+            rules: Rules::default(),
+            key_bindings: KeyBindings::default(),
+        }
+    }
+}
+
+/// Look of the map grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridStyle {
+    // Opacity of the line color of the theme.
+    pub opacity: f32,
+    // Line width in pixels.
+    pub thickness: u32,
+    // Shades every other tile with `checker_color`.
+    pub checkerboard: bool,
+    pub checker_color: [f32; 4],
+}
+
+impl Default for GridStyle {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            thickness: 2,
+            checkerboard: false,
+            checker_color: [0.0, 0.0, 0.0, 0.08],
+        }
+    }
+}
+
+/// Fullscreen effects applied to the game, the UI is drawn over them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostEffects {
+    // Glow around the snake and the apple.
+    pub bloom: bool,
+    pub color_grading: bool,
+    pub vignette: bool,
+    // Curved screen with scanlines.
+    pub crt: bool,
+    // Draws the game at a low resolution, scaled up to the window.
+    pub retro: bool,
+}
+
+/// Rendering options making the game easier to see and follow.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Accessibility {
+    pub color_mode: ColorMode,
+    // Draws the apple as a circle and marks the head with an arrow.
+    pub shape_cues: bool,
+    // Turns off animations and screen effects.
+    pub reduced_motion: bool,
+}
+
+/// Replaces the snake and apple colors of the theme with ones which
+/// stay distinguishable with a color vision deficiency.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorMode {
+    #[default]
+    Normal,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    // Replaces all colors of the theme.
+    HighContrast,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 5] = [
+        ColorMode::Normal,
+        ColorMode::Deuteranopia,
+        ColorMode::Protanopia,
+        ColorMode::Tritanopia,
+        ColorMode::HighContrast,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Normal => "NORMAL",
+            ColorMode::Deuteranopia => "DEUTERANOPIA",
+            ColorMode::Protanopia => "PROTANOPIA",
+            ColorMode::Tritanopia => "TRITANOPIA",
+            ColorMode::HighContrast => "HIGH CONTRAST",
+        }
+    }
+}
+
+/// Rules a new game is started with.
+#[derive(Debug, Clone, Copy)]
+pub struct Rules {
+    // Width and height of the map in tiles.
+    pub map_size: usize,
+    pub walls: Walls,
+    pub apple_worth: usize,
+    pub starting_length: usize,
+    // Length which wins the game, none for filling the whole board.
+    pub target_length: Option<usize>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            map_size: MAP_SIZE,
+            walls: Walls::Wrap,
+            apple_worth: APPLE_WORTH,
+            starting_length: STARTING_SNAKE_SIZE,
+            target_length: None,
+        }
+    }
+}
+
+impl Rules {
+    /// Map sizes selectable in the menu.
+    pub const MAP_SIZES: [usize; 5] = [MAP_SIZE, 30, 50, 100, 200];
+
+    /// Target lengths selectable in the menu.
+    pub const TARGET_LENGTHS: [Option<usize>; 5] =
+        [None, Some(25), Some(50), Some(100), Some(150)];
+
+    pub fn target_name(target_length: Option<usize>) -> String {
+        match target_length {
+            Some(length) => length.to_string(),
+            None => "FULL BOARD".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Walls {
+    // The snake comes out on the opposite side of the map.
+    Wrap,
+    // Hitting the map edge kills the snake.
+    Solid,
+}
+
+impl Walls {
+    pub const ALL: [Walls; 2] = [Walls::Wrap, Walls::Solid];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Walls::Wrap => "WRAP",
+            Walls::Solid => "SOLID",
+        }
+    }
+}
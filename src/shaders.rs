@@ -0,0 +1,161 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    env, fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::graphics::Graphics;
+
+// Setting this environment variable to `1` or `true` loads the shaders
+// from the source tree and reloads them when they change.
+const HOT_RELOAD_VAR: &str = "CYCLER_HOT_RELOAD";
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+// Time between checks for changed shader files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shaders built into the binary, by their path in the shader directory.
+const EMBEDDED: &[(&str, &str)] = &[
+    ("quad.wgsl", include_str!("shaders/quad.wgsl")),
+    ("grid.wgsl", include_str!("shaders/grid.wgsl")),
+    ("transition.wgsl", include_str!("shaders/transition.wgsl")),
+    ("post/common.wgsl", include_str!("shaders/post/common.wgsl")),
+    ("post/bloom.wgsl", include_str!("shaders/post/bloom.wgsl")),
+    (
+        "post/grading.wgsl",
+        include_str!("shaders/post/grading.wgsl"),
+    ),
+    (
+        "post/vignette.wgsl",
+        include_str!("shaders/post/vignette.wgsl"),
+    ),
+    ("post/crt.wgsl", include_str!("shaders/post/crt.wgsl")),
+    (
+        "post/upscale.wgsl",
+        include_str!("shaders/post/upscale.wgsl"),
+    ),
+];
+
+/// Whether shaders are loaded from disk and reloaded, see `HOT_RELOAD_VAR`.
+pub fn hot_reload_enabled() -> bool {
+    env::var(HOT_RELOAD_VAR)
+        .is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+}
+
+/// Returns the source of the shader at `path` in the shader directory,
+/// read from disk in hot reload mode. Falls back to the built-in source
+/// if the file can't be read.
+pub fn source(path: &str) -> Cow<'static, str> {
+    let embedded = EMBEDDED
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, source)| *source)
+        .unwrap_or_else(|| panic!("unknown shader {}", path));
+    if !hot_reload_enabled() {
+        return Cow::Borrowed(embedded);
+    }
+    match fs::read_to_string(shader_path(path)) {
+        Ok(source) => Cow::Owned(source),
+        Err(err) => {
+            log::error!("Failed to read shader {}: {}", path, err);
+            Cow::Borrowed(embedded)
+        }
+    }
+}
+
+/// Rebuilds a pipeline with `create`, returning none and reporting
+/// the error if a shader fails to compile, so the old pipeline can be
+/// kept instead of panicking.
+///
+/// Bind group layouts can be created again for the new pipeline: the
+/// device shares identical layouts, so the pipeline accepts the existing
+/// bind groups.
+pub fn try_rebuild<T>(
+    gfx: &Graphics,
+    label: &str,
+    create: impl FnOnce() -> T,
+) -> Option<T> {
+    gfx.device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let created = create();
+    match pollster::block_on(gfx.device.pop_error_scope()) {
+        Some(err) => {
+            log::error!("Failed to rebuild {}: {}", label, err);
+            None
+        }
+        None => {
+            log::info!("Reloaded {}", label);
+            Some(created)
+        }
+    }
+}
+
+fn shader_path(path: &str) -> PathBuf {
+    [SHADER_DIR, path].iter().collect()
+}
+
+/// Shader files changed since they were last checked.
+#[derive(Debug, Default)]
+pub struct ChangedShaders(Vec<&'static str>);
+
+impl ChangedShaders {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether any of the shaders at `paths` changed.
+    pub fn any(&self, paths: &[&str]) -> bool {
+        self.0.iter().any(|changed| paths.contains(changed))
+    }
+}
+
+/// Polls the modification times of the shader files in hot reload mode.
+pub struct ShaderWatcher {
+    modified: HashMap<&'static str, SystemTime>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    /// Starts watching the shader files, or returns none outside of hot
+    /// reload mode.
+    pub fn new() -> Option<Self> {
+        if !hot_reload_enabled() {
+            return None;
+        }
+        log::info!("Watching shaders in {}", SHADER_DIR);
+        let modified = EMBEDDED
+            .iter()
+            .filter_map(|(path, _)| Some((*path, Self::modified(path)?)))
+            .collect();
+        Some(Self {
+            modified,
+            last_poll: Instant::now(),
+        })
+    }
+
+    /// Returns the shaders changed since the last poll. Files are only
+    /// checked every `POLL_INTERVAL`.
+    pub fn poll(&mut self) -> ChangedShaders {
+        let mut changed = ChangedShaders::default();
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return changed;
+        }
+        self.last_poll = Instant::now();
+
+        for (path, _) in EMBEDDED {
+            let Some(modified) = Self::modified(path) else {
+                continue;
+            };
+            if self.modified.insert(path, modified) != Some(modified) {
+                changed.0.push(path);
+            }
+        }
+        changed
+    }
+
+    fn modified(path: &str) -> Option<SystemTime> {
+        fs::metadata(shader_path(path))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
@@ -0,0 +1,71 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Grid {
+    line_color: vec4<f32>,
+    checker_color: vec4<f32>,
+    // Width and height of the map in tiles.
+    size: vec2<f32>,
+    // Line width in pixels.
+    thickness: f32,
+    // Every other tile is shaded with `checker_color` if above 0.5.
+    checkerboard: f32,
+    // Depth of the grid layer.
+    depth: f32,
+};
+@group(1) @binding(0)
+var<uniform> grid: Grid;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Triangle strip covering the whole map, starting at the top left.
+    let corner = vec2<f32>(f32(vertex_index & 1u), 1.0 - f32(vertex_index >> 1u));
+    out.world_pos = corner * grid.size;
+    out.clip_position = camera.view_proj * vec4<f32>(out.world_pos, grid.depth, 1.0);
+
+    return out;
+}
+
+// Blends `top` over `bottom`, both with straight alpha.
+fn over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+    if (alpha <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+    let color = (top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / alpha;
+    return vec4<f32>(color, alpha);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Distance to the closest line and the size of a pixel, both in tiles.
+    let to_line = abs(in.world_pos - round(in.world_pos));
+    let pixel = fwidth(in.world_pos);
+    let half_width = 0.5 * grid.thickness * pixel;
+    // Line coverage with edges smoothed over a single pixel.
+    let coverage = 1.0 - smoothstep(half_width - 0.5 * pixel, half_width + 0.5 * pixel, to_line);
+    let line_coverage = max(coverage.x, coverage.y);
+
+    var color = vec4<f32>(0.0);
+    let tile = vec2<i32>(floor(in.world_pos));
+    if (grid.checkerboard > 0.5 && (tile.x + tile.y) % 2 == 0) {
+        color = grid.checker_color;
+    }
+    let line_color = vec4<f32>(grid.line_color.rgb, grid.line_color.a * line_coverage);
+    let out = over(line_color, color);
+    // Gaps between the lines stay out of the depth buffer.
+    if (out.a <= 0.0) {
+        discard;
+    }
+    return out;
+}
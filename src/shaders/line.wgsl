@@ -1,21 +0,0 @@
-struct VertexInput {
-    @location(0) pos: vec2<f32>,
-}
-
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-};
-
-@vertex
-fn vs_main(in: VertexInput) -> VertexOutput {
-    var out: VertexOutput;
-
-    out.clip_position = vec4<f32>(in.pos, 0.0, 1.0);
-
-    return out;
-}
-
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(0.2, 0.2, 0.2, 1.0);
-}
\ No newline at end of file
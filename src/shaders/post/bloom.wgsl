@@ -0,0 +1,32 @@
+// Spreads the light of the layers in the glow depth range, the snake and
+// the apple, over their surroundings.
+
+// Samples taken in each direction from the center.
+let BLOOM_RADIUS: i32 = 4;
+// Distance between samples in pixels.
+let BLOOM_STEP: f32 = 2.0;
+let BLOOM_STRENGTH: f32 = 0.8;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_source, s_source, in.uv, 0.0);
+    let size = vec2<i32>(params.resolution);
+
+    var glow = vec3<f32>(0.0);
+    var total: f32 = 0.0;
+    for (var y: i32 = -BLOOM_RADIUS; y <= BLOOM_RADIUS; y = y + 1) {
+        for (var x: i32 = -BLOOM_RADIUS; x <= BLOOM_RADIUS; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            let weight = exp(-dot(offset, offset) / f32(BLOOM_RADIUS * BLOOM_RADIUS));
+            let uv = in.uv + offset * BLOOM_STEP / params.resolution;
+            let pixel = clamp(vec2<i32>(uv * params.resolution), vec2<i32>(0), size - 1);
+            let depth = textureLoad(t_depth, pixel, 0);
+            if (depth >= params.glow_depth.x && depth <= params.glow_depth.y) {
+                glow = glow + textureSampleLevel(t_source, s_source, uv, 0.0).rgb * weight;
+            }
+            total = total + weight;
+        }
+    }
+
+    return vec4<f32>(color.rgb + glow / total * BLOOM_STRENGTH, color.a);
+}
@@ -0,0 +1,38 @@
+// Shared by the post-processing effects, every effect is appended to it.
+
+struct Params {
+    // Size of the game in pixels, the size of the targets.
+    resolution: vec2<f32>,
+    // Seconds since the start, frozen with reduced motion.
+    time: f32,
+    // Window pixels per game pixel, above 1 in retro mode.
+    scale: f32,
+    // Depth range of the layers which glow with bloom.
+    glow_depth: vec2<f32>,
+    // Top left corner of the scaled up game in the window.
+    offset: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var t_depth: texture_depth_2d;
+@group(0) @binding(3)
+var<uniform> params: Params;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// A single triangle covering the whole target.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
@@ -0,0 +1,27 @@
+// Old CRT screen, curved with dark scanlines and a faint band of light
+// rolling down.
+
+let CURVATURE: f32 = 0.06;
+// Height of a scanline in pixels.
+let SCANLINE_PERIOD: f32 = 3.0;
+let SCANLINE_STRENGTH: f32 = 0.25;
+let ROLL_STRENGTH: f32 = 0.04;
+let PI: f32 = 3.14159265;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Bulges the screen out, pulling the corners in.
+    var centered = in.uv * 2.0 - 1.0;
+    centered = centered * (1.0 + CURVATURE * dot(centered, centered));
+    let uv = centered * 0.5 + 0.5;
+
+    let color = textureSampleLevel(t_source, s_source, uv, 0.0);
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let row = uv.y * params.resolution.y;
+    let scanline = 1.0 - SCANLINE_STRENGTH * (0.5 + 0.5 * sin(row * 2.0 * PI / SCANLINE_PERIOD));
+    let roll = 1.0 + ROLL_STRENGTH * sin(uv.y * 6.0 - params.time * 2.0);
+    return vec4<f32>(color.rgb * scanline * roll, color.a);
+}
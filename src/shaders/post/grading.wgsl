@@ -0,0 +1,14 @@
+// Adjusts the contrast and the saturation and warms the colors up.
+
+let CONTRAST: f32 = 1.1;
+let SATURATION: f32 = 1.2;
+let TINT: vec3<f32> = vec3<f32>(1.05, 1.0, 0.92);
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_source, in.uv);
+    var rgb = (color.rgb - 0.5) * CONTRAST + 0.5;
+    let luminance = dot(rgb, vec3<f32>(0.299, 0.587, 0.114));
+    rgb = mix(vec3<f32>(luminance), rgb, SATURATION) * TINT;
+    return vec4<f32>(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+}
@@ -0,0 +1,13 @@
+// Scales the game up to the window by a whole factor, copying every pixel
+// into a square of window pixels, with black bars around it.
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = (in.clip_position.xy - params.offset) / params.scale;
+    let texel = vec2<i32>(floor(pixel));
+    let size = vec2<i32>(params.resolution);
+    if (any(texel < vec2<i32>(0, 0)) || any(texel >= size)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return textureLoad(t_source, texel, 0);
+}
@@ -0,0 +1,15 @@
+// Darkens the edges and the corners of the screen.
+
+// Distances from the center, 1.0 being the corners, where the darkening
+// starts and where it is the strongest.
+let VIGNETTE_INNER: f32 = 0.45;
+let VIGNETTE_OUTER: f32 = 1.0;
+let VIGNETTE_STRENGTH: f32 = 0.6;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_source, in.uv);
+    let distance = length(in.uv - 0.5) * sqrt(2.0);
+    let shade = 1.0 - smoothstep(VIGNETTE_INNER, VIGNETTE_OUTER, distance) * VIGNETTE_STRENGTH;
+    return vec4<f32>(color.rgb * shade, color.a);
+}
@@ -1,21 +1,68 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Palette {
+    colors: array<vec4<f32>, 8>,
+    // 1.0 when textures are recolored instead of tinted.
+    recolor: f32,
+};
+@group(2) @binding(0)
+var<uniform> palette: Palette;
+
+// Depth of the layer being drawn, see `LayerFilter`.
+struct LayerFilter {
+    depth: f32,
+};
+@group(3) @binding(0)
+var<uniform> layer: LayerFilter;
+
 struct VertexInput {
     @builtin(vertex_index) vertex_index: u32,
     @location(0) top_left: vec3<f32>,
     @location(1) bottom_right: vec2<f32>,
     @location(2) tex_top_left: vec2<f32>,
-    @location(3) tex_bottom_right: vec2<f32>
+    @location(3) tex_bottom_right: vec2<f32>,
+    // Index of the palette color the texture is tinted with.
+    @location(4) palette_index: u32,
+    @location(5) color: vec4<f32>,
+    // Counterclockwise rotation around the center in radians.
+    @location(6) rotation: f32,
+    @location(7) corner_radius: f32,
+    @location(8) shape: u32,
 }
 
+// Values of `QuadShape`, any other shape is a plain rectangle.
+let SHAPE_TEXTURED: u32 = 0u;
+let SHAPE_ROUNDED_RECT: u32 = 2u;
+let SHAPE_CIRCLE: u32 = 3u;
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coord: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+    @location(2) recolor: f32,
+    @location(3) color: vec4<f32>,
+    // Position relative to the center of the quad, before rotation.
+    @location(4) local: vec2<f32>,
+    @location(5) @interpolate(flat) half_size: vec2<f32>,
+    @location(6) @interpolate(flat) corner_radius: f32,
+    @location(7) @interpolate(flat) shape: u32,
 };
 
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
+    out.tint = palette.colors[in.palette_index];
+    // The neutral color at index 0 keeps the texture as it is.
+    out.recolor = select(0.0, palette.recolor, in.palette_index != 0u);
+    out.color = in.color;
+    out.corner_radius = in.corner_radius;
+    out.shape = in.shape;
 
-    var pos: vec2<f32>;
+    var corner: vec2<f32>;
     var left: f32 = in.top_left.x;
     var right: f32 = in.bottom_right.x;
     var top: f32 = in.top_left.y;
@@ -23,34 +70,99 @@ fn vs_main(in: VertexInput) -> VertexOutput {
 
     switch(i32(in.vertex_index)) {
         case 0: {
-            pos = vec2<f32>(left, top);
+            corner = vec2<f32>(left, top);
             out.tex_coord = in.tex_top_left;
             break;
         }
         case 1: {
-            pos = vec2<f32>(right, top);
+            corner = vec2<f32>(right, top);
             out.tex_coord = vec2<f32>(in.tex_bottom_right.x, in.tex_top_left.y);
             break;
         }
         case 2: {
-            pos = vec2<f32>(left, bottom);
+            corner = vec2<f32>(left, bottom);
             out.tex_coord = vec2<f32>(in.tex_top_left.x, in.tex_bottom_right.y);
             break;
         }
         case 3: {
-            pos = vec2<f32>(right, bottom);
+            corner = vec2<f32>(right, bottom);
             out.tex_coord = in.tex_bottom_right;
             break;
         }
         default: {}
     }
 
-    out.clip_position = vec4<f32>(pos, 0.0, 1.0);
+    let center = (in.top_left.xy + in.bottom_right) * 0.5;
+    out.local = corner - center;
+    out.half_size = abs(in.bottom_right - in.top_left.xy) * 0.5;
+    let c = cos(in.rotation);
+    let s = sin(in.rotation);
+    let rotated = vec2<f32>(
+        out.local.x * c - out.local.y * s,
+        out.local.x * s + out.local.y * c,
+    );
+    // The z component is the depth of the layer of the quad.
+    out.clip_position = camera.view_proj * vec4<f32>(center + rotated, in.top_left.z, 1.0);
+
+    // Quads outside of the view are culled by collapsing them into
+    // a single point. The bounds cover the quad at any rotation.
+    let radius = length(out.half_size);
+    let clip_min = camera.view_proj * vec4<f32>(center - radius, 0.0, 1.0);
+    let clip_max = camera.view_proj * vec4<f32>(center + radius, 0.0, 1.0);
+    let bounds_min = min(clip_min.xy, clip_max.xy);
+    let bounds_max = max(clip_min.xy, clip_max.xy);
+    // Quads of the other layers are culled the same way.
+    if (any(bounds_max < vec2<f32>(-1.0)) || any(bounds_min > vec2<f32>(1.0))
+        || in.top_left.z != layer.depth) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
 
     return out;
 }
 
+@group(1) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var s_atlas: sampler;
+
+// Signed distance from the edge of a rectangle with rounded corners,
+// negative inside.
+fn rounded_rect_distance(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let r = min(radius, min(half_size.x, half_size.y));
+    let q = abs(p) - half_size + r;
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - r;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.tex_coord, 1.0, 1.0);
-}
\ No newline at end of file
+    // Textures and derivatives need uniform control flow, so everything
+    // is computed for every shape and then selected.
+    let texel = textureSample(t_atlas, s_atlas, in.tex_coord);
+    let radius = select(0.0, in.corner_radius, in.shape == SHAPE_ROUNDED_RECT);
+    let distance = select(
+        rounded_rect_distance(in.local, in.half_size, radius),
+        length(in.local) - min(in.half_size.x, in.half_size.y),
+        in.shape == SHAPE_CIRCLE,
+    );
+    // Edges are anti-aliased over about a pixel.
+    let coverage = clamp(0.5 - distance / max(fwidth(distance), 0.0001), 0.0, 1.0);
+
+    var color = in.color;
+    if (in.shape == SHAPE_TEXTURED) {
+        color = color * texel;
+    } else {
+        color.a = color.a * coverage;
+    }
+
+    // Recoloring keeps the shading of the texture, dark outlines stay
+    // dark and everything from mid gray up takes on the full color.
+    let shade = clamp(dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114)) * 2.0, 0.0, 1.0);
+    let rgb = mix(color.rgb, vec3<f32>(shade), in.recolor);
+    let out = vec4<f32>(rgb, color.a) * in.tint;
+    // Fully transparent pixels are left out of the depth buffer, so
+    // post-processing only sees what is drawn.
+    if (out.a <= 0.0) {
+        discard;
+    }
+    return out;
+}
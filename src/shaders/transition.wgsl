@@ -0,0 +1,49 @@
+// Covers the screen between two states of the engine.
+
+struct TransitionUniform {
+    // Size of the window in pixels.
+    resolution: vec2<f32>,
+    // Center of the iris in pixels.
+    center: vec2<f32>,
+    // Fraction of the screen covered, from 0 to 1.
+    coverage: f32,
+    // 0 fades, 1 wipes from the left, 2 closes an iris on the center.
+    kind: u32,
+    color: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> transition: TransitionUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// A single triangle covering the whole window.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// Width of the soft edge of wipes and irises in pixels.
+let EDGE: f32 = 2.0;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = in.clip_position.xy;
+    var covered = transition.coverage;
+    if (transition.kind == 1u) {
+        let edge = transition.coverage * (transition.resolution.x + EDGE);
+        covered = clamp((edge - pixel.x) / EDGE, 0.0, 1.0);
+    } else if (transition.kind == 2u) {
+        // Distance to the farthest corner, so the open iris shows
+        // the whole window.
+        let far = max(transition.center, transition.resolution - transition.center);
+        let radius = (1.0 - transition.coverage) * (length(far) + EDGE);
+        covered = clamp((distance(pixel, transition.center) - radius) / EDGE + 0.5, 0.0, 1.0);
+    }
+    return vec4<f32>(transition.color.rgb, transition.color.a * covered);
+}
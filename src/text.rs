@@ -0,0 +1,266 @@
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    batch::{BatchSlot, QuadBatch},
+    camera::Camera,
+    graphics::{Graphics, Layer, Quad, Renderable},
+    shaders::ChangedShaders,
+    texture::Texture,
+    theme::{Palette, PaletteColor, Theme},
+};
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+// Glyph cells are padded by one texel to avoid bleeding between glyphs.
+const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+const FONT_COLUMNS: u32 = 16;
+// The glyph slot grows as more text is queued.
+const INITIAL_GLYPHS: usize = 256;
+
+/// 5x7 bitmap font, every row of a glyph is stored in the lower 5 bits.
+/// Lowercase letters are drawn as uppercase and unknown characters as `?`.
+#[rustfmt::skip]
+const GLYPHS: &[(char, [u8; 7])] = &[
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('[', [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110]),
+    (']', [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+    ('*', [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000]),
+    ('\'', [0b00100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111]),
+];
+
+/// Bitmap font baked into a texture at startup.
+pub struct Font {
+    texture_width: u32,
+    texture_height: u32,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Font {
+    pub fn new(gfx: &Graphics, layout: &wgpu::BindGroupLayout) -> Self {
+        let rows = (GLYPHS.len() as u32).div_ceil(FONT_COLUMNS);
+        let texture_width = FONT_COLUMNS * CELL_WIDTH;
+        let texture_height = rows * CELL_HEIGHT;
+
+        let mut image = RgbaImage::new(texture_width, texture_height);
+        for (index, (_, glyph_rows)) in GLYPHS.iter().enumerate() {
+            let (cell_x, cell_y) = Self::cell_origin(index);
+            for (y, row) in glyph_rows.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        image.put_pixel(
+                            cell_x + x,
+                            cell_y + y as u32,
+                            Rgba([255, 255, 255, 255]),
+                        );
+                    }
+                }
+            }
+        }
+
+        let texture = Texture::from_image(
+            gfx,
+            &image,
+            "Font Texture",
+            wgpu::FilterMode::Nearest,
+        );
+        let bind_group = texture.bind_group(gfx, layout);
+
+        Self {
+            texture_width,
+            texture_height,
+            bind_group,
+        }
+    }
+
+    fn cell_origin(index: usize) -> (u32, u32) {
+        let index = index as u32;
+        (
+            (index % FONT_COLUMNS) * CELL_WIDTH,
+            (index / FONT_COLUMNS) * CELL_HEIGHT,
+        )
+    }
+
+    fn glyph_index(c: char) -> usize {
+        let c = c.to_ascii_uppercase();
+        GLYPHS
+            .iter()
+            .position(|(glyph, _)| *glyph == c)
+            .or_else(|| GLYPHS.iter().position(|(glyph, _)| *glyph == '?'))
+            .unwrap()
+    }
+
+    /// Returns the top left and bottom right texture coordinates of
+    /// the glyph.
+    fn tex_coords(&self, c: char) -> ([f32; 2], [f32; 2]) {
+        let (x, y) = Self::cell_origin(Self::glyph_index(c));
+        let width = self.texture_width as f32;
+        let height = self.texture_height as f32;
+
+        (
+            [x as f32 / width, y as f32 / height],
+            [
+                (x + GLYPH_WIDTH) as f32 / width,
+                (y + GLYPH_HEIGHT) as f32 / height,
+            ],
+        )
+    }
+}
+
+/// Queues text in pixel coordinates and draws it with the quad pipeline.
+///
+/// Text is queued every frame with [`TextRenderer::queue`] and uploaded
+/// with [`TextRenderer::prepare`] before rendering.
+pub struct TextRenderer {
+    font: Font,
+    pipeline: wgpu::RenderPipeline,
+    glyphs: Vec<Quad>,
+
+    // Maps pixel positions to clip space.
+    camera: Camera,
+    palette: Palette,
+    batch: QuadBatch,
+    slot: BatchSlot,
+}
+
+impl TextRenderer {
+    pub fn new(gfx: &Graphics, theme: &Theme) -> Self {
+        let camera_layout = Camera::bind_group_layout(gfx);
+        let texture_layout = Texture::bind_group_layout(gfx);
+        let palette_layout = Palette::bind_group_layout(gfx);
+        let font = Font::new(gfx, &texture_layout);
+        let pipeline = Quad::create_pipeline(
+            gfx,
+            &camera_layout,
+            &texture_layout,
+            &palette_layout,
+        );
+        let camera = Camera::new(gfx, &camera_layout);
+        let palette = Palette::new(gfx, &palette_layout, theme);
+
+        let mut batch = QuadBatch::new(gfx, "Text Quad Buffer");
+        let slot = batch.allocate(INITIAL_GLYPHS);
+
+        Self {
+            font,
+            pipeline,
+            glyphs: Vec::new(),
+            camera,
+            palette,
+            batch,
+            slot,
+        }
+    }
+
+    pub fn set_theme(&self, gfx: &Graphics, theme: &Theme) {
+        self.palette.set_theme(gfx, theme);
+    }
+
+    pub fn reload_shaders(&mut self, gfx: &Graphics, changed: &ChangedShaders) {
+        Quad::reload_pipeline(gfx, &mut self.pipeline, changed);
+    }
+
+    /// Returns the width and height of the text in pixels.
+    pub fn measure(text: &str, scale: f32) -> [f32; 2] {
+        let chars = text.chars().count() as f32;
+        [
+            chars * CELL_WIDTH as f32 * scale,
+            GLYPH_HEIGHT as f32 * scale,
+        ]
+    }
+
+    /// Queues the text with its top left corner at `pos` (in pixels,
+    /// from the top left of the window). `scale` is the size of a font
+    /// texel in pixels.
+    pub fn queue(&mut self, text: &str, pos: [f32; 2], scale: f32) {
+        for (i, c) in text.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let left = pos[0] + (i as u32 * CELL_WIDTH) as f32 * scale;
+            let right = left + GLYPH_WIDTH as f32 * scale;
+            let top = pos[1];
+            let bottom = top + GLYPH_HEIGHT as f32 * scale;
+            let (tex_top_left, tex_bottom_right) = self.font.tex_coords(c);
+
+            self.glyphs.push(Quad {
+                top_left: [left, top, Layer::Ui.depth()],
+                bottom_right: [right, bottom],
+                tex_top_left,
+                tex_bottom_right,
+                palette: PaletteColor::Ui as u32,
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Uploads the queued text and clears the queue for the next frame.
+    pub fn prepare(&mut self, gfx: &Graphics) {
+        self.camera.screen(gfx);
+        self.batch.write(self.slot, &self.glyphs);
+        self.batch.upload(gfx);
+        self.glyphs.clear();
+    }
+}
+
+impl Renderable for TextRenderer {
+    fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.camera.bind_group, &[]);
+        rpass.set_bind_group(1, &self.font.bind_group, &[]);
+        rpass.set_bind_group(2, &self.palette.bind_group, &[]);
+        self.batch.render(rpass);
+    }
+}
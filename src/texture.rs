@@ -0,0 +1,287 @@
+use image::{Rgba, RgbaImage};
+
+use crate::{graphics::Graphics, theme::PaletteColor};
+
+pub struct Texture {
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Texture {
+    /// Decodes an image drawn on an opaque white background, pure white
+    /// is treated as transparent.
+    pub fn decode(bytes: &[u8]) -> RgbaImage {
+        let mut image = image::load_from_memory(bytes).unwrap().to_rgba8();
+        for pixel in image.pixels_mut() {
+            if pixel.0[..3] == [255, 255, 255] {
+                pixel.0[3] = 0;
+            }
+        }
+        image
+    }
+
+    pub fn from_image(
+        gfx: &Graphics,
+        image: &RgbaImage,
+        label: &str,
+        filter: wgpu::FilterMode,
+    ) -> Self {
+        let (width, height) = image.dimensions();
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = gfx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Texture Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let texture = Self {
+            texture,
+            view,
+            sampler,
+            width,
+            height,
+        };
+        texture.write(gfx, image);
+        texture
+    }
+
+    /// Replaces the contents of the texture, `image` has to be
+    /// of the same size.
+    pub fn write(&self, gfx: &Graphics, image: &image::RgbaImage) {
+        assert_eq!(image.dimensions(), (self.width, self.height));
+        gfx.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            image,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * self.width),
+                rows_per_image: std::num::NonZeroU32::new(self.height),
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    pub fn bind_group_layout(gfx: &Graphics) -> wgpu::BindGroupLayout {
+        gfx.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Texture Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+            })
+    }
+
+    pub fn bind_group(
+        &self,
+        gfx: &Graphics,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+}
+
+pub const ATLAS_WIDTH: u32 = 1000;
+pub const ATLAS_HEIGHT: u32 = 600;
+// The arrow marking the head is drawn into the empty bottom left
+// corner of the atlas, in a square cell of this size.
+const ARROW_SIZE: u32 = 96;
+const ARROW_TOP: u32 = 400;
+const ARROW_FILL: Rgba<u8> = Rgba([30, 30, 30, 255]);
+const ARROW_OUTLINE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// Sprites found in `res/texture_prototype.png`, and the arrow drawn
+/// into the atlas at startup.
+///
+/// Snake pieces are named after the directions they connect to,
+/// e.g. `CornerUpLeft` joins the segment above and the segment to the left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sprite {
+    HeadUp,
+    HeadDown,
+    HeadLeft,
+    HeadRight,
+    // Tails are named after the direction the body continues in.
+    TailUp,
+    TailDown,
+    TailLeft,
+    TailRight,
+    Horizontal,
+    Vertical,
+    CornerUpLeft,
+    CornerUpRight,
+    CornerDownLeft,
+    CornerDownRight,
+    Apple,
+    // Arrow pointing up, rotated to mark the direction of the head.
+    Arrow,
+}
+
+impl Sprite {
+    /// Pixel rectangle (left, top, right, bottom) of the sprite in the atlas.
+    /// A left edge bigger than the right edge mirrors the sprite.
+    fn rect(&self) -> [u32; 4] {
+        match self {
+            Sprite::HeadUp => [134, 46, 210, 137],
+            Sprite::HeadDown => [686, 145, 763, 241],
+            Sprite::HeadLeft => [799, 55, 671, 133],
+            Sprite::HeadRight => [671, 55, 799, 133],
+            Sprite::TailUp => [243, 205, 295, 269],
+            Sprite::TailDown => [182, 285, 252, 355],
+            Sprite::TailLeft => [139, 211, 235, 302],
+            Sprite::TailRight => [252, 290, 348, 360],
+            Sprite::Horizontal => [28, 148, 114, 215],
+            Sprite::Vertical => [40, 250, 114, 345],
+            Sprite::CornerUpLeft => [499, 200, 622, 317],
+            Sprite::CornerUpRight => [307, 185, 418, 298],
+            Sprite::CornerDownLeft => [492, 65, 608, 165],
+            Sprite::CornerDownRight => [313, 55, 418, 163],
+            Sprite::Apple => [405, 281, 495, 367],
+            Sprite::Arrow => [0, ARROW_TOP, ARROW_SIZE, ARROW_TOP + ARROW_SIZE],
+        }
+    }
+
+    /// Color of the theme the sprite is tinted with.
+    pub fn palette_color(&self) -> PaletteColor {
+        match self {
+            Sprite::HeadUp
+            | Sprite::HeadDown
+            | Sprite::HeadLeft
+            | Sprite::HeadRight => PaletteColor::Head,
+            Sprite::Apple => PaletteColor::Apple,
+            Sprite::Arrow => PaletteColor::Neutral,
+            _ => PaletteColor::Body,
+        }
+    }
+
+    /// Returns the top left and bottom right texture coordinates of
+    /// the sprite.
+    pub fn tex_coords(&self) -> ([f32; 2], [f32; 2]) {
+        let [left, top, right, bottom] = self.rect();
+        let width = ATLAS_WIDTH as f32;
+        let height = ATLAS_HEIGHT as f32;
+
+        (
+            [left as f32 / width, top as f32 / height],
+            [right as f32 / width, bottom as f32 / height],
+        )
+    }
+}
+
+pub struct Atlas {
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Atlas {
+    pub fn new(gfx: &Graphics, layout: &wgpu::BindGroupLayout) -> Self {
+        let mut image =
+            Texture::decode(include_bytes!("../res/texture_prototype.png"));
+        draw_arrow(&mut image);
+        let texture = Texture::from_image(
+            gfx,
+            &image,
+            "Atlas Texture",
+            wgpu::FilterMode::Linear,
+        );
+        assert_eq!(
+            (texture.width, texture.height),
+            (ATLAS_WIDTH, ATLAS_HEIGHT)
+        );
+        let bind_group = texture.bind_group(gfx, layout);
+
+        Self { bind_group }
+    }
+}
+
+/// Draws the arrow pointing up into its cell, a dark triangle on
+/// a short shaft outlined in white.
+fn draw_arrow(image: &mut RgbaImage) {
+    let size = ARROW_SIZE as f32;
+    for y in 0..ARROW_SIZE {
+        for x in 0..ARROW_SIZE {
+            let (fx, fy) = ((x as f32 + 0.5) / size, (y as f32 + 0.5) / size);
+            // Tests the arrow grown by `grow` on every side.
+            let inside = |grow: f32| {
+                let triangle = fy < 0.58 + grow
+                    && fy > 0.15 + 1.6 * (fx - 0.5).abs() - grow * 2.0;
+                let shaft = (fx - 0.5).abs() < 0.1 + grow
+                    && fy > 0.5
+                    && fy < 0.82 + grow;
+                triangle || shaft
+            };
+            let color = if inside(0.0) {
+                ARROW_FILL
+            } else if inside(0.05) {
+                ARROW_OUTLINE
+            } else {
+                continue;
+            };
+            image.put_pixel(x, ARROW_TOP + y, color);
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use std::fs;
+
+use wgpu::util::DeviceExt;
+
+use crate::{graphics::Graphics, settings::ColorMode};
+
+const THEMES_FILE: &str = "themes.txt";
+const PALETTE_SIZE: usize = 8;
+
+/// Colors of a [`Theme`] the quads can be tinted with, selected by
+/// the `palette` index of a quad.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u32)]
+pub enum PaletteColor {
+    // Leaves the texture as it is.
+    Neutral,
+    Head,
+    Body,
+    Apple,
+    Ui,
+}
+
+/// Named set of colors for the whole game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub background: [f32; 4],
+    pub grid: [f32; 4],
+    pub head: [f32; 4],
+    pub body: [f32; 4],
+    pub apple: [f32; 4],
+    pub ui: [f32; 4],
+    // Replaces the colors of the sprites with the palette colors instead
+    // of tinting them, keeping only their shading.
+    pub recolor: bool,
+}
+
+impl Theme {
+    pub fn builtin() -> Vec<Theme> {
+        let white = [1.0, 1.0, 1.0, 1.0];
+        vec![
+            Theme {
+                name: "CLASSIC".to_string(),
+                background: rgb(0xa0, 0xe8, 0x20),
+                grid: rgb(0x33, 0x33, 0x33),
+                head: white,
+                body: white,
+                apple: white,
+                ui: white,
+                recolor: false,
+            },
+            Theme {
+                name: "NIGHT".to_string(),
+                background: rgb(0x10, 0x14, 0x28),
+                grid: rgb(0x50, 0x5a, 0x80),
+                head: rgb(0xb0, 0xff, 0xb0),
+                body: rgb(0x90, 0xe0, 0x90),
+                apple: rgb(0xff, 0xa0, 0xa0),
+                ui: rgb(0xd0, 0xd8, 0xff),
+                recolor: false,
+            },
+            Theme {
+                name: "SAND".to_string(),
+                background: rgb(0xe8, 0xd8, 0xa8),
+                grid: rgb(0x8a, 0x70, 0x48),
+                head: rgb(0xff, 0xe0, 0xa0),
+                body: rgb(0xe0, 0xc0, 0x80),
+                apple: white,
+                ui: rgb(0x40, 0x30, 0x20),
+                recolor: false,
+            },
+        ]
+    }
+
+    /// Returns the built-in themes followed by the ones from the themes
+    /// file in the working directory. Themes from the file replace
+    /// built-in themes with the same name.
+    pub fn load_all() -> Vec<Theme> {
+        let mut themes = Self::builtin();
+        let contents = match fs::read_to_string(THEMES_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return themes,
+        };
+        for theme in Self::parse(&contents) {
+            match themes.iter_mut().find(|t| t.name == theme.name) {
+                Some(existing) => *existing = theme,
+                None => themes.push(theme),
+            }
+        }
+        themes
+    }
+
+    /// Parses themes in the format
+    ///
+    /// ```text
+    /// [NAME]
+    /// background = #rrggbb
+    /// grid = #rrggbbaa
+    /// ```
+    ///
+    /// Colors not listed are taken from the first built-in theme.
+    /// Invalid lines are reported and skipped.
+    fn parse(contents: &str) -> Vec<Theme> {
+        let mut themes: Vec<Theme> = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) =
+                line.strip_prefix('[').and_then(|l| l.strip_suffix(']'))
+            {
+                let mut theme = Self::builtin().swap_remove(0);
+                theme.name = name.trim().to_uppercase();
+                themes.push(theme);
+                continue;
+            }
+
+            let parsed = line.split_once('=').and_then(|(key, value)| {
+                let theme = themes.last_mut()?;
+                let color = parse_color(value.trim())?;
+                let field = match key.trim() {
+                    "background" => &mut theme.background,
+                    "grid" => &mut theme.grid,
+                    "head" => &mut theme.head,
+                    "body" => &mut theme.body,
+                    "apple" => &mut theme.apple,
+                    "ui" => &mut theme.ui,
+                    _ => return None,
+                };
+                *field = color;
+                Some(())
+            });
+            if parsed.is_none() {
+                log::error!(
+                    "{}:{}: invalid line: {}",
+                    THEMES_FILE,
+                    number + 1,
+                    line
+                );
+            }
+        }
+        themes
+    }
+
+    /// Returns the theme with the snake and apple colors replaced by
+    /// colors safe for the color mode, based on the Okabe-Ito palette.
+    pub fn with_color_mode(&self, mode: ColorMode) -> Theme {
+        let mut theme = self.clone();
+        let (head, body, apple) = match mode {
+            ColorMode::Normal => return theme,
+            ColorMode::Deuteranopia => (
+                rgb(0x56, 0xb4, 0xe9),
+                rgb(0x00, 0x72, 0xb2),
+                rgb(0xe6, 0x9f, 0x00),
+            ),
+            ColorMode::Protanopia => (
+                rgb(0x56, 0xb4, 0xe9),
+                rgb(0x00, 0x72, 0xb2),
+                rgb(0xf0, 0xe4, 0x42),
+            ),
+            ColorMode::Tritanopia => (
+                rgb(0xcc, 0x79, 0xa7),
+                rgb(0x00, 0x9e, 0x73),
+                rgb(0xd5, 0x5e, 0x00),
+            ),
+            ColorMode::HighContrast => {
+                theme.background = rgb(0x00, 0x00, 0x00);
+                theme.grid = rgb(0xff, 0xff, 0xff);
+                theme.ui = rgb(0xff, 0xff, 0xff);
+                (
+                    rgb(0xff, 0xff, 0x00),
+                    rgb(0xff, 0xff, 0xff),
+                    rgb(0xff, 0x00, 0xff),
+                )
+            }
+        };
+        theme.head = head;
+        theme.body = body;
+        theme.apple = apple;
+        theme.recolor = true;
+        theme
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.background.map(|c| c as f64);
+        wgpu::Color { r, g, b, a }
+    }
+
+    fn palette(&self) -> [[f32; 4]; PALETTE_SIZE] {
+        let mut colors = [[1.0; 4]; PALETTE_SIZE];
+        colors[PaletteColor::Head as usize] = self.head;
+        colors[PaletteColor::Body as usize] = self.body;
+        colors[PaletteColor::Apple as usize] = self.apple;
+        colors[PaletteColor::Ui as usize] = self.ui;
+        colors
+    }
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> [f32; 4] {
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0]
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa`.
+fn parse_color(value: &str) -> Option<[f32; 4]> {
+    let hex = value.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8)
+        || !hex.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    let channel = |i: usize| {
+        hex.get(i * 2..i * 2 + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+    };
+    let alpha = if hex.len() == 8 { channel(3)? } else { 255 };
+    Some([
+        channel(0)? as f32 / 255.0,
+        channel(1)? as f32 / 255.0,
+        channel(2)? as f32 / 255.0,
+        alpha as f32 / 255.0,
+    ])
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PaletteUniform {
+    colors: [[f32; 4]; PALETTE_SIZE],
+    recolor: f32,
+    _padding: [f32; 3],
+}
+
+impl PaletteUniform {
+    fn new(theme: &Theme) -> Self {
+        Self {
+            colors: theme.palette(),
+            recolor: if theme.recolor { 1.0 } else { 0.0 },
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// Uniform buffer with the colors of the current theme.
+pub struct Palette {
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Palette {
+    pub fn bind_group_layout(gfx: &Graphics) -> wgpu::BindGroupLayout {
+        gfx.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Palette Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            })
+    }
+
+    pub fn new(
+        gfx: &Graphics,
+        layout: &wgpu::BindGroupLayout,
+        theme: &Theme,
+    ) -> Self {
+        let buffer =
+            gfx.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Palette Buffer"),
+                    contents: bytemuck::cast_slice(&[PaletteUniform::new(
+                        theme,
+                    )]),
+                    usage: wgpu::BufferUsages::UNIFORM
+                        | wgpu::BufferUsages::COPY_DST,
+                });
+        let bind_group =
+            gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Palette Bind Group"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+
+        Self { buffer, bind_group }
+    }
+
+    pub fn set_theme(&self, gfx: &Graphics, theme: &Theme) {
+        gfx.queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[PaletteUniform::new(theme)]),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(
+            parse_color("#ff8000"),
+            Some([1.0, 128.0 / 255.0, 0.0, 1.0])
+        );
+        assert_eq!(
+            parse_color("#FF800000"),
+            Some([1.0, 128.0 / 255.0, 0.0, 0.0])
+        );
+        assert_eq!(parse_color("#a0e820"), Some(rgb(0xa0, 0xe8, 0x20)));
+    }
+
+    #[test]
+    fn bad_colors() {
+        for value in [
+            "",
+            "#",
+            "ff8000",
+            "#ff80",
+            "#ff80001",
+            "#ff8000000",
+            "#gg8000",
+            "#+1+2+3",
+            "#ff80é0",
+        ] {
+            assert_eq!(parse_color(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn themes_file() {
+        let themes = Theme::parse(
+            "# Comment\n\
+             [ ocean ]\n\
+             background = #103050\n\
+             grid=#80c0ff80\n\
+             \n\
+             [SAND]\n\
+             apple = #ff0000\n",
+        );
+        let classic = Theme::builtin().swap_remove(0);
+        assert_eq!(themes.len(), 2);
+        assert_eq!(themes[0].name, "OCEAN");
+        assert_eq!(themes[0].background, rgb(0x10, 0x30, 0x50));
+        assert_eq!(
+            themes[0].grid,
+            [128.0 / 255.0, 192.0 / 255.0, 1.0, 128.0 / 255.0]
+        );
+        assert_eq!(themes[1].name, "SAND");
+        assert_eq!(themes[1].apple, rgb(0xff, 0x00, 0x00));
+        // Missing keys are taken from the classic theme.
+        assert_eq!(themes[0].head, classic.head);
+        assert_eq!(themes[1].background, classic.background);
+    }
+
+    #[test]
+    fn invalid_lines_skipped() {
+        let themes = Theme::parse(
+            "head = #ff0000\n\
+             [BAD]\n\
+             head = red\n\
+             tail = #ff0000\n\
+             body #ff0000\n\
+             apple = #00ff00\n",
+        );
+        let classic = Theme::builtin().swap_remove(0);
+        assert_eq!(themes.len(), 1);
+        assert_eq!(themes[0].head, classic.head);
+        assert_eq!(themes[0].body, classic.body);
+        assert_eq!(themes[0].apple, rgb(0x00, 0xff, 0x00));
+    }
+}
@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    graphics::Graphics,
+    shaders::{self, ChangedShaders},
+};
+
+// Time taken to cover the screen and to uncover it again, in seconds.
+const HALF_TIME: f32 = 0.3;
+const COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const SHADER: &str = "transition.wgsl";
+
+/// How the screen is covered, see `shaders/transition.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u32)]
+pub enum TransitionKind {
+    Fade,
+    // Sweeps across the screen from the left.
+    Wipe,
+    // Circle closing on the head of the snake and opening from it.
+    Iris,
+}
+
+/// Switch between two states, covering the screen and uncovering it
+/// again once `target` took over.
+///
+/// Runs on the frame time, input is ignored until it finishes.
+#[derive(Debug)]
+pub struct Transition<T> {
+    pub kind: TransitionKind,
+    elapsed: f32,
+    // Taken when the screen is fully covered.
+    target: Option<T>,
+}
+
+impl<T> Transition<T> {
+    pub fn new(kind: TransitionKind, target: T) -> Self {
+        Self {
+            kind,
+            elapsed: 0.0,
+            target: Some(target),
+        }
+    }
+
+    /// Advances the transition by `frame_time`. Returns the target once,
+    /// in the frame the screen gets fully covered.
+    pub fn advance(&mut self, frame_time: Duration) -> Option<T> {
+        self.elapsed += frame_time.as_secs_f32();
+        if self.elapsed >= HALF_TIME {
+            self.target.take()
+        } else {
+            None
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= HALF_TIME * 2.0
+    }
+
+    /// Fraction of the screen covered, rising to 1 and falling back to 0.
+    pub fn coverage(&self) -> f32 {
+        let t = 1.0 - (self.elapsed / HALF_TIME - 1.0).abs();
+        let t = t.clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TransitionUniform {
+    resolution: [f32; 2],
+    center: [f32; 2],
+    coverage: f32,
+    kind: u32,
+    _padding: [u32; 2],
+    color: [f32; 4],
+}
+
+/// Draws transitions over the whole window.
+pub struct TransitionRenderer {
+    pipeline: wgpu::RenderPipeline,
+    pipeline_layout: wgpu::PipelineLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TransitionRenderer {
+    pub fn new(gfx: &Graphics) -> Self {
+        let layout = gfx.device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("Transition Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            },
+        );
+        let buffer =
+            gfx.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Transition Buffer"),
+                    contents: bytemuck::cast_slice(&[Self::uniform(
+                        gfx,
+                        TransitionKind::Fade,
+                        0.0,
+                        [0.0; 2],
+                    )]),
+                    usage: wgpu::BufferUsages::UNIFORM
+                        | wgpu::BufferUsages::COPY_DST,
+                });
+        let bind_group =
+            gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Transition Bind Group"),
+                layout: &layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+
+        let pipeline_layout = gfx.device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Transition Pipeline Layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            },
+        );
+        let pipeline = Self::create_pipeline(gfx, &pipeline_layout);
+
+        Self {
+            pipeline,
+            pipeline_layout,
+            buffer,
+            bind_group,
+        }
+    }
+
+    /// Rebuilds the pipeline if its shader changed. The pipeline is kept
+    /// if the shader fails to compile.
+    pub fn reload_shaders(&mut self, gfx: &Graphics, changed: &ChangedShaders) {
+        if !changed.any(&[SHADER]) {
+            return;
+        }
+        let rebuilt = shaders::try_rebuild(gfx, "Transition Pipeline", || {
+            Self::create_pipeline(gfx, &self.pipeline_layout)
+        });
+        if let Some(rebuilt) = rebuilt {
+            self.pipeline = rebuilt;
+        }
+    }
+
+    /// Draws the transition over `output`, an iris being centered on
+    /// `center` in pixels from the top left corner of the window.
+    pub fn draw<T>(
+        &self,
+        gfx: &Graphics,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        transition: &Transition<T>,
+        center: [f32; 2],
+    ) {
+        gfx.queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[Self::uniform(
+                gfx,
+                transition.kind,
+                transition.coverage(),
+                center,
+            )]),
+        );
+
+        let mut rpass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transition Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+
+    fn create_pipeline(
+        gfx: &Graphics,
+        layout: &wgpu::PipelineLayout,
+    ) -> wgpu::RenderPipeline {
+        let shader_module =
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(SHADER),
+                    source: wgpu::ShaderSource::Wgsl(shaders::source(SHADER)),
+                });
+        gfx.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Transition Pipeline"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: gfx.config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            })
+    }
+
+    fn uniform(
+        gfx: &Graphics,
+        kind: TransitionKind,
+        coverage: f32,
+        center: [f32; 2],
+    ) -> TransitionUniform {
+        TransitionUniform {
+            resolution: [gfx.config.width as f32, gfx.config.height as f32],
+            center,
+            coverage,
+            kind: kind as u32,
+            _padding: [0; 2],
+            color: COLOR,
+        }
+    }
+}
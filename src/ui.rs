@@ -0,0 +1,222 @@
+use std::ops::RangeInclusive;
+
+use winit::event::VirtualKeyCode;
+
+use crate::{graphics::Graphics, input::InputManager, text::TextRenderer};
+
+const TITLE_SCALE: f32 = 4.0;
+const ITEM_SCALE: f32 = 2.0;
+// Height of a row as a multiple of the text height.
+const ROW_SPACING: f32 = 2.0;
+const SLIDER_WIDTH: u32 = 10;
+
+/// Focus of a menu, kept in between frames.
+#[derive(Debug, Default)]
+pub struct UiState {
+    focused: usize,
+    // Number of interactive items laid out in the last frame.
+    items: usize,
+}
+
+impl UiState {
+    pub fn reset(&mut self) {
+        self.focused = 0;
+    }
+}
+
+/// Interaction with a single item during this frame.
+struct Response {
+    activated: bool,
+    // -1 or 1 when the value of the item should be decreased or increased.
+    adjust: i32,
+}
+
+/// Immediate mode UI drawn with bitmap text.
+///
+/// Items are laid out top to bottom, centered horizontally. The focused item
+/// is moved with the up and down keys or by hovering it with the mouse.
+/// Enter, space or a click activates it, left and right keys (or clicking
+/// the left or right half of the item) change its value.
+pub struct Ui<'a> {
+    gfx: &'a Graphics,
+    text: &'a mut TextRenderer,
+    state: &'a mut UiState,
+
+    key: Option<VirtualKeyCode>,
+    cursor_pos: Option<[f32; 2]>,
+    clicked: bool,
+
+    y: f32,
+    items: usize,
+}
+
+impl<'a> Ui<'a> {
+    pub fn begin(
+        gfx: &'a Graphics,
+        text: &'a mut TextRenderer,
+        state: &'a mut UiState,
+        input: Option<&InputManager>,
+    ) -> Self {
+        let key = input.and_then(InputManager::get_pressed_key);
+        if state.items > 0 {
+            match key {
+                Some(VirtualKeyCode::Up) => {
+                    state.focused =
+                        (state.focused + state.items - 1) % state.items
+                }
+                Some(VirtualKeyCode::Down) => {
+                    state.focused = (state.focused + 1) % state.items
+                }
+                _ => (),
+            }
+        }
+
+        Self {
+            gfx,
+            text,
+            state,
+            key,
+            cursor_pos: input.and_then(InputManager::get_cursor_pos),
+            clicked: input.is_some_and(InputManager::is_mouse_clicked),
+            y: gfx.config.height as f32 * 0.1,
+            items: 0,
+        }
+    }
+
+    pub fn title(&mut self, text: &str) {
+        self.centered_row(text, TITLE_SCALE);
+    }
+
+    pub fn label(&mut self, text: &str) {
+        self.centered_row(text, ITEM_SCALE);
+    }
+
+    pub fn button(&mut self, label: &str) -> bool {
+        self.item(label).activated
+    }
+
+    /// Returns `true` if the value has changed.
+    pub fn toggle(&mut self, label: &str, value: &mut bool) -> bool {
+        let state = if *value { "ON" } else { "OFF" };
+        let response = self.item(&format!("{}: {}", label, state));
+        if response.activated || response.adjust != 0 {
+            *value = !*value;
+            return true;
+        }
+        false
+    }
+
+    /// Returns `true` if the value has changed.
+    pub fn slider(
+        &mut self,
+        label: &str,
+        value: &mut u32,
+        range: RangeInclusive<u32>,
+    ) -> bool {
+        let (min, max) = (*range.start(), *range.end());
+        let filled = if max > min {
+            (*value - min) * SLIDER_WIDTH / (max - min)
+        } else {
+            SLIDER_WIDTH
+        };
+        let bar: String = (0..SLIDER_WIDTH)
+            .map(|i| if i < filled { '#' } else { '-' })
+            .collect();
+
+        let response = self.item(&format!("{}: [{}] {}", label, bar, *value));
+        let new_value = (*value as i64 + response.adjust as i64)
+            .clamp(min as i64, max as i64) as u32;
+        let changed = new_value != *value;
+        *value = new_value;
+        changed
+    }
+
+    /// Returns `true` if the selection has changed.
+    pub fn list(
+        &mut self,
+        label: &str,
+        selected: &mut usize,
+        options: &[&str],
+    ) -> bool {
+        let response =
+            self.item(&format!("{}: < {} >", label, options[*selected]));
+        let adjust = match response {
+            Response {
+                adjust: 0,
+                activated: true,
+            } => 1,
+            Response { adjust, .. } => adjust,
+        };
+        if adjust == 0 {
+            return false;
+        }
+        let count = options.len() as i32;
+        *selected = ((*selected as i32 + adjust).rem_euclid(count)) as usize;
+        true
+    }
+
+    /// Adds vertical space between items.
+    pub fn space(&mut self) {
+        self.y += TextRenderer::measure("", ITEM_SCALE)[1] * ROW_SPACING;
+    }
+
+    pub fn finish(self) {
+        self.state.items = self.items;
+        if self.state.focused >= self.items {
+            self.state.focused = 0;
+        }
+    }
+
+    fn centered_row(&mut self, text: &str, scale: f32) {
+        let size = TextRenderer::measure(text, scale);
+        let pos = [(self.gfx.config.width as f32 - size[0]) / 2.0, self.y];
+        self.text.queue(text, pos, scale);
+        self.y += size[1] * ROW_SPACING;
+    }
+
+    fn item(&mut self, text: &str) -> Response {
+        let index = self.items;
+        self.items += 1;
+
+        let unfocused_size = TextRenderer::measure(text, ITEM_SCALE);
+        let left = (self.gfx.config.width as f32 - unfocused_size[0]) / 2.0;
+        let hovered = self.cursor_pos.filter(|[x, y]| {
+            (left..=left + unfocused_size[0]).contains(x)
+                && (self.y..=self.y + unfocused_size[1]).contains(y)
+        });
+        if hovered.is_some() {
+            self.state.focused = index;
+        }
+
+        let focused = self.state.focused == index;
+        if focused {
+            self.centered_row(&format!("> {} <", text), ITEM_SCALE);
+        } else {
+            self.centered_row(text, ITEM_SCALE);
+        }
+
+        let mut response = Response {
+            activated: false,
+            adjust: 0,
+        };
+        if focused {
+            match self.key {
+                Some(VirtualKeyCode::Return | VirtualKeyCode::Space) => {
+                    response.activated = true
+                }
+                Some(VirtualKeyCode::Left) => response.adjust = -1,
+                Some(VirtualKeyCode::Right) => response.adjust = 1,
+                _ => (),
+            }
+        }
+        if let (Some([x, _]), true) = (hovered, self.clicked) {
+            response.activated = true;
+            response.adjust = if x < left + unfocused_size[0] / 2.0 {
+                -1
+            } else {
+                1
+            };
+        }
+        response
+    }
+}